@@ -0,0 +1,77 @@
+// Most of the included modules go unused here, and their test modules lose
+// every `#[test]` item outside the test harness.
+#![allow(dead_code, unused_imports)]
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use image::{Rgb, RgbImage};
+use palette::{FromColor, Hsv, Srgb};
+
+// rwal is a binary crate, so the bench compiles the modules `prepare_colors`
+// depends on directly.
+#[path = "../src/backends/mod.rs"]
+mod backends;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/dirs.rs"]
+mod dirs;
+#[path = "../src/harmony.rs"]
+mod harmony;
+#[path = "../src/http.rs"]
+mod http;
+#[path = "../src/orientation.rs"]
+mod orientation;
+#[path = "../src/pages.rs"]
+mod pages;
+#[path = "../src/rwal.rs"]
+mod rwal;
+
+// A 512x512 thumbnail with only 16 distinct colors, the case the memo in
+// `prepare_colors` is meant to speed up.
+fn low_color_image() -> RgbImage {
+    RgbImage::from_fn(512, 512, |x, y| {
+        let i = ((x / 32) + (y / 128) * 4) as u8 % 16;
+        Rgb([i * 16, 255 - i * 16, i * 8])
+    })
+}
+
+// A 200x200 thumbnail with 3,200 colors that differ only in blue and the high
+// bits of green, which a hasher with poorly mixed low bits sends to the same
+// buckets.
+fn many_color_image() -> RgbImage {
+    RgbImage::from_fn(200, 200, |x, y| {
+        let i = (y * 200 + x) % 3200;
+        Rgb([128, ((i / 256) as u8) << 4, (i % 256) as u8])
+    })
+}
+
+// Per-pixel conversion with no memo, the baseline the memo has to beat.
+fn direct(image: &RgbImage) -> Vec<Hsv> {
+    image
+        .pixels()
+        .map(|p| Hsv::from_color(Srgb::new(p[0], p[1], p[2]).into_format::<f32>()))
+        .collect()
+}
+
+fn bench_hsv(c: &mut Criterion) {
+    let rwal = rwal::Rwal::from(&config::Config::default());
+
+    let mut group = c.benchmark_group("hsv");
+    for (name, image) in [("16", low_color_image()), ("3200", many_color_image())] {
+        group.bench_function(format!("direct/{}", name), |b| {
+            b.iter(|| direct(black_box(&image)))
+        });
+        group.bench_function(format!("prepare_colors/{}", name), |b| {
+            b.iter_batched(
+                || image.clone(),
+                |image| rwal.prepare_colors(black_box(image)),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_hsv);
+criterion_main!(benches);
@@ -5,29 +5,38 @@ use std::str::FromStr;
 
 pub struct Flag {
     descriptions: HashMap<String, (String, Type)>,
-    values: HashMap<String, String>,
+    values: HashMap<String, Vec<String>>,
     args: Vec<String>,
     help_message_prefix: Option<String>,
 }
 
 impl Default for Flag {
     fn default() -> Self {
+        Flag::from_args(env::args())
+    }
+}
+
+impl Flag {
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Self {
         let descriptions = HashMap::new();
-        let mut values = HashMap::new();
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
 
-        let args = env::args();
+        let args: Vec<String> = args.into_iter().collect();
 
         let mut f = None;
 
-        for arg in args {
-            let is_flag = arg.starts_with("-");
+        for arg in &args {
+            let is_flag = is_flag(arg);
             let has_val = arg.contains("=");
 
             if is_flag && has_val {
                 let mut parts = arg.splitn(2, "=");
                 if let Some(flag) = parts.next() {
                     let val = parts.next().unwrap_or("");
-                    values.insert(flag.to_owned(), val.to_owned());
+                    values
+                        .entry(flag.to_owned())
+                        .or_default()
+                        .push(val.to_owned());
                 }
                 continue;
             }
@@ -38,14 +47,14 @@ impl Default for Flag {
             }
 
             if let Some(flag) = f.take() {
-                values.insert(flag, arg.to_owned());
+                values.entry(flag).or_default().push(arg.to_owned());
             }
         }
 
         Flag {
             descriptions,
             values,
-            args: env::args().collect(),
+            args,
             help_message_prefix: None,
         }
     }
@@ -103,71 +112,132 @@ impl Flag {
         self.help_message_prefix = Some(msg.to_string())
     }
 
+    pub fn validate_known(&self, known: &[&str]) -> Vec<String> {
+        self.args
+            .iter()
+            .skip(1)
+            .filter(|arg| is_flag(arg))
+            .map(|arg| arg.split("=").next().unwrap_or(arg))
+            .filter(|flag| !known.contains(flag))
+            .map(|flag| flag.to_string())
+            .collect()
+    }
+
     pub fn get_bool(&mut self, key: &str) -> bool {
-        self.args.contains(&key.to_string())
+        matches!(self.get_opt_bool(key), Ok(Some(true)))
+    }
+
+    pub fn get_opt_bool(&mut self, key: &str) -> Result<Option<bool>, String> {
+        let prefix = format!("{key}=");
+        let Some(arg) = self
+            .args
+            .iter()
+            .skip(1)
+            .rev()
+            .find(|arg| *arg == key || arg.starts_with(&prefix))
+        else {
+            return Ok(None);
+        };
+
+        match arg.strip_prefix(&prefix) {
+            Some(v) => v
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid value for {}: {}", key, v)),
+            None => Ok(Some(true)),
+        }
+    }
+
+    fn value(&self, key: &str) -> Option<&String> {
+        self.values.get(key).and_then(|v| v.last())
+    }
+
+    pub fn get_vec(&mut self, key: &str) -> Vec<String> {
+        self.values
+            .get(key)
+            .map(|v| {
+                v.iter()
+                    .flat_map(|s| s.split(","))
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_values(&mut self, key: &str, count: usize) -> Vec<String> {
+        let Some(start) = self.args.iter().rposition(|arg| arg == key) else {
+            return Vec::new();
+        };
+
+        self.args[start + 1..]
+            .iter()
+            .take_while(|arg| !is_flag(arg))
+            .take(count)
+            .cloned()
+            .collect()
     }
 
     pub fn get_str(&mut self, key: &str) -> Option<String> {
-        self.values.get(key).map(|s| s.to_string())
+        self.value(key).map(|s| s.to_string())
     }
 
     pub fn fget_str(&mut self, key: &str, fallback: &str, desc: &str) -> String {
         self.add_desc(key, desc, Type::String);
-        self.values
-            .get(key)
+        self.value(key)
             .map(|s| s.to_string())
             .unwrap_or(fallback.to_string())
     }
 
     pub fn get_i32(&mut self, key: &str) -> Option<i32> {
-        self.values.get(key).and_then(|i| i.parse().ok())
+        self.value(key).and_then(|i| i.parse().ok())
     }
 
     pub fn fget_i32(&mut self, key: &str, fallback: i32, desc: &str) -> i32 {
-        self.values
-            .get(key)
+        self.value(key)
             .and_then(|i| i.parse().ok())
             .unwrap_or(fallback)
     }
 
     pub fn get_u32(&mut self, key: &str) -> Option<u32> {
-        self.values.get(key).and_then(|u| u.parse().ok())
+        self.value(key).and_then(|u| u.parse().ok())
     }
 
     pub fn fget_u32(&mut self, key: &str, fallback: u32, desc: &str) -> u32 {
         self.add_desc(key, desc, Type::Unsigned);
-        self.values
-            .get(key)
+        self.value(key)
             .and_then(|u| u.parse().ok())
             .unwrap_or(fallback)
     }
 
     pub fn get_f32(&mut self, key: &str) -> Option<f32> {
-        self.values.get(key).and_then(|f| f.parse().ok())
+        self.value(key).and_then(|f| f.parse().ok())
     }
 
     pub fn fget_f32(&mut self, key: &str, fallback: f32, desc: &str) -> f32 {
         self.add_desc(key, desc, Type::Double);
-        self.values
-            .get(key)
+        self.value(key)
             .and_then(|f| f.parse().ok())
             .unwrap_or(fallback)
     }
 
     pub fn get<T: FromStr>(&mut self, key: &str, desc: &str) -> Option<T> {
         self.add_desc(key, desc, Type::Complex);
-        self.values.get(key).and_then(|f| f.parse().ok())
+        self.value(key).and_then(|f| f.parse().ok())
     }
 
     pub fn fget<T: FromStr>(&mut self, key: &str, fallback: T, desc: &str) -> T {
         self.add_desc(key, desc, Type::Complex);
-        self.values
-            .get(key)
+        self.value(key)
             .and_then(|f| f.parse().ok())
             .unwrap_or(fallback)
     }
 }
 
+fn is_flag(arg: &str) -> bool {
+    arg.starts_with("-") && arg.parse::<f64>().is_err()
+}
+
 #[derive(Debug)]
 enum Type {
     String,
@@ -191,3 +261,92 @@ impl Display for Type {
         .fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag(args: &[&str]) -> Flag {
+        Flag::from_args(
+            std::iter::once("rwal")
+                .chain(args.iter().copied())
+                .map(String::from),
+        )
+    }
+
+    #[test]
+    fn bool_flag_forms() {
+        assert_eq!(
+            flag(&["--skip-value"]).get_opt_bool("--skip-value"),
+            Ok(Some(true))
+        );
+        assert_eq!(
+            flag(&["--skip-value=true"]).get_opt_bool("--skip-value"),
+            Ok(Some(true))
+        );
+        assert_eq!(
+            flag(&["--skip-value=false"]).get_opt_bool("--skip-value"),
+            Ok(Some(false))
+        );
+        assert_eq!(flag(&[]).get_opt_bool("--skip-value"), Ok(None));
+        assert!(!flag(&["--skip-value=false"]).get_bool("--skip-value"));
+    }
+
+    #[test]
+    fn positional_values() {
+        let mut f = flag(&["--compare", "a.png", "b.png", "-l"]);
+        assert_eq!(f.get_values("--compare", 2), ["a.png", "b.png"]);
+        assert_eq!(f.get_values("--compare", 1), ["a.png"]);
+        assert!(f.get_values("-l", 2).is_empty());
+        assert!(f.get_values("--missing", 2).is_empty());
+    }
+
+    #[test]
+    fn last_bool_flag_wins() {
+        assert_eq!(
+            flag(&["--skip-value", "--skip-value=false"]).get_opt_bool("--skip-value"),
+            Ok(Some(false))
+        );
+        assert_eq!(
+            flag(&["--skip-value=false", "--skip-value"]).get_opt_bool("--skip-value"),
+            Ok(Some(true))
+        );
+    }
+
+    #[test]
+    fn malformed_bool_flags_are_errors() {
+        assert_eq!(
+            flag(&["--skip-value", "--skip-value=maybe"]).get_opt_bool("--skip-value"),
+            Err("Invalid value for --skip-value: maybe".to_string())
+        );
+        assert!(!flag(&["--skip-value=maybe"]).get_bool("--skip-value"));
+    }
+
+    #[test]
+    fn unknown_flags_are_reported() {
+        let f = flag(&["--thum-w", "50", "-i", "a.png", "--thumb=3"]);
+        assert_eq!(f.validate_known(&["-i", "--thumb"]), ["--thum-w"]);
+        assert!(f.validate_known(&["-i", "--thumb", "--thum-w"]).is_empty());
+    }
+
+    #[test]
+    fn negative_numbers_are_values() {
+        let mut f = flag(&["--foo", "-1.5", "-x=-3", "--bar", "-2"]);
+        assert_eq!(f.get_f32("--foo"), Some(-1.5));
+        assert_eq!(f.get_i32("-x"), Some(-3));
+        assert_eq!(f.get_i32("--bar"), Some(-2));
+        assert!(f.validate_known(&["--foo", "-x", "--bar"]).is_empty());
+    }
+
+    #[test]
+    fn repeated_and_comma_separated_values() {
+        assert_eq!(flag(&["-i", "a", "-i", "b"]).get_vec("-i"), ["a", "b"]);
+        assert_eq!(flag(&["-i", "a,b"]).get_vec("-i"), ["a", "b"]);
+        assert_eq!(flag(&["-i", "a,b", "-i=c"]).get_vec("-i"), ["a", "b", "c"]);
+        assert!(flag(&[]).get_vec("-i").is_empty());
+        assert_eq!(
+            flag(&["-i", "a", "-i", "b"]).get_str("-i"),
+            Some("b".into())
+        );
+    }
+}
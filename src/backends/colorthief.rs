@@ -1,18 +1,32 @@
 use super::RwalBackend;
 
-pub struct ColorThief;
+pub struct ColorThief {
+    pub quality: u8,
+}
 
 impl RwalBackend for ColorThief {
-    fn generate_palette(&self, colors: &[(u8, u8, u8)], _: usize) -> Option<Vec<(u8, u8, u8)>> {
+    fn generate_palette(&self, colors: &[(u8, u8, u8)], count: usize) -> Option<Vec<(u8, u8, u8)>> {
         let pixels = colors
             .iter()
             .map(|c| [c.0, c.1, c.2])
             .collect::<Vec<[u8; 3]>>()
             .concat();
 
-        let colors =
-            color_thief::get_palette(&pixels, color_thief::ColorFormat::Rgb, 5, 255).ok()?;
+        let max_colors = (count + 1).clamp(2, 255) as u8;
+        let colors = color_thief::get_palette(
+            &pixels,
+            color_thief::ColorFormat::Rgb,
+            self.quality,
+            max_colors,
+        )
+        .ok()?;
 
-        Some(colors.into_iter().map(|c| (c.r, c.g, c.b)).collect())
+        Some(
+            colors
+                .into_iter()
+                .take(count)
+                .map(|c| (c.r, c.g, c.b))
+                .collect(),
+        )
     }
 }
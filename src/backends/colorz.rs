@@ -1,5 +1,4 @@
-use std::cmp::Ordering;
-
+use kmeans_colors::Kmeans;
 use kmeans_colors::get_kmeans;
 
 use palette::IntoColor;
@@ -7,11 +6,14 @@ use palette::Lab;
 use palette::Srgb;
 
 use super::RwalBackend;
+use super::WeightedColor;
 
-pub struct ColorZ;
+pub struct ColorZ {
+    pub runs: u32,
+}
 
-impl RwalBackend for ColorZ {
-    fn generate_palette(&self, colors: &[(u8, u8, u8)], count: usize) -> Option<Vec<(u8, u8, u8)>> {
+impl ColorZ {
+    fn clusters(&self, colors: &[(u8, u8, u8)], count: usize, seed: u64) -> Option<Kmeans<Lab>> {
         if colors.is_empty() {
             return None;
         }
@@ -24,18 +26,45 @@ impl RwalBackend for ColorZ {
             })
             .collect();
 
-        let clusters = (0..3)
-            .map(|i| get_kmeans(count, 100, 0.001, false, &lab_colors, 64 + i as u64))
-            .min_by(|a, b| a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal))
-            .unwrap();
+        let runs = self.runs.max(1) as u64;
+
+        (0..runs)
+            .map(|i| {
+                (
+                    i,
+                    get_kmeans(count, 100, 0.001, false, &lab_colors, 64 + seed * runs + i),
+                )
+            })
+            .filter(|(_, kmeans)| !kmeans.score.is_nan())
+            .min_by(|(i, a), (j, b)| a.score.total_cmp(&b.score).then(i.cmp(j)))
+            .map(|(_, kmeans)| kmeans)
+    }
+}
+
+fn to_rgb(centroid: &Lab) -> (u8, u8, u8) {
+    let srgb: Srgb = (*centroid).into_color();
+    let srgb_u8 = srgb.into_format::<u8>();
+
+    (srgb_u8.red, srgb_u8.green, srgb_u8.blue)
+}
+
+impl RwalBackend for ColorZ {
+    fn generate_palette(&self, colors: &[(u8, u8, u8)], count: usize) -> Option<Vec<(u8, u8, u8)>> {
+        self.generate_palette_seeded(colors, count, 0)
+    }
+
+    fn generate_palette_seeded(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        seed: u64,
+    ) -> Option<Vec<(u8, u8, u8)>> {
+        let clusters = self.clusters(colors, count, seed)?;
 
         let mut palette_colors = Vec::with_capacity(count);
 
         for centroid in &clusters.centroids {
-            let srgb: Srgb = (*centroid).into_color();
-            let srgb_u8 = srgb.into_format::<u8>();
-
-            palette_colors.push((srgb_u8.red, srgb_u8.green, srgb_u8.blue));
+            palette_colors.push(to_rgb(centroid));
         }
 
         while palette_colors.len() < count {
@@ -44,4 +73,29 @@ impl RwalBackend for ColorZ {
 
         Some(palette_colors)
     }
+
+    fn generate_palette_weighted(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        seed: u64,
+    ) -> Option<Vec<WeightedColor>> {
+        let clusters = self.clusters(colors, count, seed)?;
+
+        let mut sizes = vec![0usize; clusters.centroids.len()];
+        for &index in &clusters.indices {
+            sizes[index as usize] += 1;
+        }
+
+        let total = clusters.indices.len().max(1) as f32;
+
+        Some(
+            clusters
+                .centroids
+                .iter()
+                .zip(sizes)
+                .map(|(centroid, size)| (to_rgb(centroid), size as f32 / total))
+                .collect(),
+        )
+    }
 }
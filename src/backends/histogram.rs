@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+
+use super::RwalBackend;
+
+pub struct Histogram;
+
+const BITS: u8 = 5;
+
+impl RwalBackend for Histogram {
+    fn generate_palette(&self, colors: &[(u8, u8, u8)], count: usize) -> Option<Vec<(u8, u8, u8)>> {
+        if colors.is_empty() {
+            return None;
+        }
+
+        let shift = 8 - BITS;
+        let mut buckets: HashMap<(u8, u8, u8), (usize, [u64; 3])> = HashMap::new();
+
+        for &(r, g, b) in colors {
+            let bucket = buckets
+                .entry((r >> shift, g >> shift, b >> shift))
+                .or_insert((0, [0; 3]));
+
+            bucket.0 += 1;
+            bucket.1[0] += r as u64;
+            bucket.1[1] += g as u64;
+            bucket.1[2] += b as u64;
+        }
+
+        let mut buckets: Vec<(usize, (u8, u8, u8))> = buckets
+            .into_values()
+            .map(|(n, [r, g, b])| {
+                let n64 = n as u64;
+                (n, ((r / n64) as u8, (g / n64) as u8, (b / n64) as u8))
+            })
+            .collect();
+
+        buckets.sort_by(|f, s| s.0.cmp(&f.0).then(f.1.cmp(&s.1)));
+
+        Some(buckets.into_iter().take(count).map(|(_, c)| c).collect())
+    }
+}
@@ -1,44 +1,152 @@
 pub mod colorthief;
 pub mod colorz;
+pub mod histogram;
+
+use std::fmt::Display;
+use std::str::FromStr;
 
 use serde::Deserialize;
+use serde::Serialize;
+
+pub type WeightedColor = ((u8, u8, u8), f32);
 
 pub trait RwalBackend {
     fn generate_palette(&self, colors: &[(u8, u8, u8)], count: usize) -> Option<Vec<(u8, u8, u8)>>;
+
+    fn generate_palette_seeded(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        _seed: u64,
+    ) -> Option<Vec<(u8, u8, u8)>> {
+        self.generate_palette(colors, count)
+    }
+
+    fn generate_palette_weighted(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        seed: u64,
+    ) -> Option<Vec<WeightedColor>> {
+        let palette = self.generate_palette_seeded(colors, count, seed)?;
+        let weight = 1.0 / palette.len().max(1) as f32;
+
+        Some(palette.into_iter().map(|color| (color, weight)).collect())
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Backend {
     #[default]
     ColorZ,
     Colorthief,
+    Histogram,
 }
 
-impl From<String> for Backend {
-    fn from(value: String) -> Self {
-        match value.to_string().as_str() {
-            "colorthief" | "ColorThief" => Backend::Colorthief,
-            _ => Backend::ColorZ,
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "colorz" | "ColorZ" => Ok(Backend::ColorZ),
+            "colorthief" | "ColorThief" => Ok(Backend::Colorthief),
+            "histogram" | "Histogram" => Ok(Backend::Histogram),
+            _ => Err(format!("Unknown backend: {}", s)),
         }
     }
 }
 
-impl ToString for Backend {
-    fn to_string(&self) -> String {
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Backend::Colorthief => "colorthief",
             Backend::ColorZ => "colorz",
+            Backend::Histogram => "histogram",
         }
-        .to_string()
+        .fmt(f)
     }
 }
 
-impl RwalBackend for Backend {
-    fn generate_palette(&self, colors: &[(u8, u8, u8)], count: usize) -> Option<Vec<(u8, u8, u8)>> {
+impl Backend {
+    pub fn build(self, colorthief_quality: u8, kmeans_runs: u32) -> Box<dyn RwalBackend + Send> {
         match self {
-            Backend::ColorZ => colorz::ColorZ.generate_palette(colors, count),
-            Backend::Colorthief => colorthief::ColorThief.generate_palette(colors, count),
+            Backend::ColorZ => Box::new(colorz::ColorZ { runs: kmeans_runs }),
+            Backend::Colorthief => Box::new(colorthief::ColorThief {
+                quality: colorthief_quality,
+            }),
+            Backend::Histogram => Box::new(histogram::Histogram),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_names() {
+        for backend in [Backend::ColorZ, Backend::Colorthief, Backend::Histogram] {
+            assert_eq!(backend.to_string().parse::<Backend>(), Ok(backend));
+        }
+        assert_eq!("ColorThief".parse::<Backend>(), Ok(Backend::Colorthief));
+    }
+
+    #[test]
+    fn unknown_backend_is_an_error() {
+        assert_eq!(
+            "colortheif".parse::<Backend>(),
+            Err("Unknown backend: colortheif".to_string())
+        );
+        assert!("".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn colorthief_quality_is_its_sampling_step() {
+        // Every tenth pixel is red, so only a step of 10 sees nothing but red.
+        let colors = (0..1000)
+            .map(|i| {
+                if i % 10 == 0 {
+                    (200, 0, 0)
+                } else {
+                    (0, 0, 200)
+                }
+            })
+            .collect::<Vec<_>>();
+        let dominant = |quality| {
+            Backend::Colorthief
+                .build(quality, 1)
+                .generate_palette(&colors, 2)
+                .unwrap()[0]
+        };
+
+        let (r, _, b) = dominant(1);
+        assert!(b > r, "{:?}", (r, b));
+        let (r, _, b) = dominant(10);
+        assert!(r > b, "{:?}", (r, b));
+    }
+
+    #[test]
+    fn colorz_ties_pick_the_same_run() {
+        // Four exact colors give every run the same score, so only the
+        // tie-break decides which run's centroids are returned.
+        let colors = (0..400)
+            .map(|i| [(200, 30, 30), (30, 200, 30), (30, 30, 200), (220, 220, 220)][i % 4])
+            .collect::<Vec<_>>();
+        let backend = Backend::ColorZ.build(5, 3);
+
+        for seed in [0, 7] {
+            let first = backend.generate_palette_seeded(&colors, 4, seed);
+            assert!(first.is_some());
+            for _ in 0..5 {
+                assert_eq!(backend.generate_palette_seeded(&colors, 4, seed), first);
+            }
+
+            let weighted = backend.generate_palette_weighted(&colors, 4, seed);
+            assert_eq!(
+                backend.generate_palette_weighted(&colors, 4, seed),
+                weighted
+            );
         }
     }
 }
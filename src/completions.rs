@@ -0,0 +1,477 @@
+pub enum Value {
+    None,
+    Any,
+    File,
+    Choice(&'static [&'static str]),
+}
+
+pub const FLAGS: &[(&str, &str, Value)] = &[
+    ("-v", "verbose logging", Value::None),
+    ("-q", "quite logging", Value::None),
+    (
+        "--log-format",
+        "log format",
+        Value::Choice(&["pretty", "json"]),
+    ),
+    (
+        "-i",
+        "image/path-with-images to generate colorscheme from",
+        Value::File,
+    ),
+    (
+        "--from-color",
+        "generate the colorscheme from a color",
+        Value::Any,
+    ),
+    (
+        "--repeat-last",
+        "generate from the image of the last run",
+        Value::None,
+    ),
+    (
+        "--pick-seed",
+        "seed for picking a directory image",
+        Value::Any,
+    ),
+    ("-l", "generate light colorscheme", Value::None),
+    ("-c", "skip cache", Value::None),
+    ("--force", "regenerate and overwrite the cache", Value::None),
+    ("-o", "also write the colors to this path", Value::File),
+    ("--out", "also write the colors to this path", Value::File),
+    (
+        "--format",
+        "format of the -o output",
+        Value::Choice(&[
+            "hex",
+            "labeled",
+            "xresources",
+            "windows-terminal",
+            "base16",
+            "base24",
+        ]),
+    ),
+    (
+        "--hex-case",
+        "case of hex colors in the -o output",
+        Value::Choice(&["lower", "upper"]),
+    ),
+    (
+        "--color-notation",
+        "notation of colors in the -o output",
+        Value::Choice(&["hex", "rgb"]),
+    ),
+    ("--accent", "print the accent color", Value::None),
+    ("--explain", "print how each color was derived", Value::None),
+    (
+        "--count-distinct",
+        "print how many colors survive filtering",
+        Value::None,
+    ),
+    ("--average", "print the average image color", Value::None),
+    (
+        "--palette-weights",
+        "print the raw clusters as json",
+        Value::None,
+    ),
+    (
+        "--check-contrast",
+        "print the contrast against the background",
+        Value::None,
+    ),
+    (
+        "--strict-contrast",
+        "fail if a color has too little contrast",
+        Value::None,
+    ),
+    ("--hook", "run this command after writing", Value::Any),
+    (
+        "--hook-on-cache",
+        "also run the hook on cache hits",
+        Value::None,
+    ),
+    ("--no-preview", "do not write the html preview", Value::None),
+    (
+        "--update-terminals",
+        "send the colors to every open terminal",
+        Value::None,
+    ),
+    (
+        "--json-summary",
+        "print a json summary of the run",
+        Value::None,
+    ),
+    (
+        "--set-wallpaper",
+        "also set the image as the wallpaper",
+        Value::Choice(&["feh", "swww", "hyprpaper", "swaybg"]),
+    ),
+    (
+        "--cache-info",
+        "print the cached metadata for an image",
+        Value::File,
+    ),
+    (
+        "--phash-threshold",
+        "reuse the cache of near-identical images",
+        Value::Any,
+    ),
+    (
+        "--compare",
+        "compare the colorschemes of two images",
+        Value::File,
+    ),
+    ("--restore", "reapply the current colorscheme", Value::None),
+    (
+        "--precache",
+        "cache a colorscheme for every image in a directory",
+        Value::File,
+    ),
+    ("-h", "show help", Value::None),
+    ("--help", "show help", Value::None),
+    (
+        "--generate-completions",
+        "print a completion script",
+        Value::Choice(&["bash", "zsh", "fish"]),
+    ),
+    ("--config", "read config from this file", Value::File),
+    ("--stdin-config", "read config from stdin", Value::None),
+    (
+        "--init-config",
+        "write the default config and exit",
+        Value::None,
+    ),
+    ("--preset", "apply a preset from the config", Value::Any),
+    ("--fast", "preset favoring speed", Value::None),
+    ("--quality", "preset favoring quality", Value::None),
+    (
+        "--backend",
+        "set backend",
+        Value::Choice(&["colorz", "colorthief", "histogram"]),
+    ),
+    (
+        "--passthrough",
+        "use the image colors as is if there are few",
+        Value::None,
+    ),
+    ("--kmeans-runs", "kmeans runs of colorz", Value::Any),
+    (
+        "--colorthief-quality",
+        "colorthief sampling step",
+        Value::Any,
+    ),
+    ("--timeout", "backend timeout in seconds", Value::Any),
+    (
+        "--max-retries",
+        "rerun the backend with new seeds",
+        Value::Any,
+    ),
+    ("--seed", "seed for the backend and sampling", Value::Any),
+    ("--thumb", "set thumb width and height", Value::Any),
+    (
+        "--thumb-scale",
+        "size the thumb relative to the image",
+        Value::Any,
+    ),
+    (
+        "--max-pixels",
+        "size the thumb to at most this many pixels",
+        Value::Any,
+    ),
+    ("--thumb-w", "set thumb width", Value::Any),
+    ("--thumb-h", "set thumb height", Value::Any),
+    (
+        "--preserve-aspect",
+        "keep the thumb aspect ratio",
+        Value::None,
+    ),
+    ("--page", "page or frame of multi-page images", Value::Any),
+    (
+        "--respect-exif",
+        "rotate photos by their exif orientation",
+        Value::None,
+    ),
+    (
+        "--center-weight",
+        "favor pixels near the image center",
+        Value::Any,
+    ),
+    (
+        "--edge-weight",
+        "favor pixels with strong gradients",
+        Value::Any,
+    ),
+    (
+        "--sample-corners",
+        "only sample pixels near the edges",
+        Value::None,
+    ),
+    (
+        "--corner-margin",
+        "width of the edge band in percent",
+        Value::Any,
+    ),
+    ("--sample-stride", "only sample every nth pixel", Value::Any),
+    (
+        "--sample-random",
+        "only sample this many random pixels",
+        Value::Any,
+    ),
+    ("--crop", "crop the image to x,y,w,h", Value::Any),
+    ("--crop-center", "crop the image to its center", Value::Any),
+    ("--clamp-s-min", "set min saturation clamp", Value::Any),
+    ("--clamp-s-max", "set max saturation clamp", Value::Any),
+    ("--clamp-v-min", "set min value clamp", Value::Any),
+    ("--clamp-v-max", "set max value clamp", Value::Any),
+    ("--skip-s-min", "set min saturation skip", Value::Any),
+    ("--skip-s-max", "set max saturation skip", Value::Any),
+    ("--skip-v-min", "set min value skip", Value::Any),
+    ("--skip-v-max", "set max value skip", Value::Any),
+    ("--skip-value", "skip value", Value::None),
+    ("--skip-saturation", "skip saturation", Value::None),
+    (
+        "--skip-invert",
+        "remove the colors inside the skip ranges",
+        Value::None,
+    ),
+    (
+        "--skip-inclusive",
+        "keep colors exactly at the skip bounds",
+        Value::None,
+    ),
+    ("--clamp-value", "clamp value", Value::None),
+    ("--clamp-saturation", "clamp saturation", Value::None),
+    (
+        "--clamp-mode",
+        "how values are clamped",
+        Value::Choice(&["hard", "soft"]),
+    ),
+    (
+        "--filter-space",
+        "space the skip/clamp bounds apply in",
+        Value::Choice(&["hsv", "hsl", "lab"]),
+    ),
+    (
+        "--exclude-extremes",
+        "drop near-black and near-white pixels",
+        Value::None,
+    ),
+    (
+        "--exclude-dark",
+        "value threshold for near-black",
+        Value::Any,
+    ),
+    (
+        "--exclude-light",
+        "whiteness threshold for near-white",
+        Value::Any,
+    ),
+    ("--bg-idx", "palette color to mix with bg", Value::Any),
+    ("--fg-idx", "palette color to mix with fg", Value::Any),
+    (
+        "--auto-bg-fg",
+        "pick bg/fg palette colors by luminance",
+        Value::None,
+    ),
+    (
+        "--bg-str",
+        "amount of palette color to apply to bg",
+        Value::Any,
+    ),
+    (
+        "--fg-str",
+        "amount of palette color to apply to fg",
+        Value::Any,
+    ),
+    ("--bg", "background color", Value::Any),
+    (
+        "--bg-from-corner",
+        "sample the background from the image",
+        Value::Choice(&["topleft", "topright", "bottomleft", "bottomright", "center"]),
+    ),
+    ("--fg", "foreground color", Value::Any),
+    ("--cursor", "cursor color", Value::Any),
+    ("--cursor-idx", "color to use as the cursor", Value::Any),
+    (
+        "--bg-mode",
+        "how bg is derived",
+        Value::Choice(&["fixed", "darken", "lighten"]),
+    ),
+    (
+        "--fg-mode",
+        "how fg is derived",
+        Value::Choice(&["fixed", "darken", "lighten"]),
+    ),
+    ("--base-only", "only write color0-7", Value::None),
+    (
+        "--base-theme",
+        "merge the generated colors into this theme",
+        Value::File,
+    ),
+    (
+        "--merge-indices",
+        "generated colors to merge into the base theme",
+        Value::Any,
+    ),
+    ("--linear-mix", "mix colors in linear light", Value::None),
+    (
+        "--temperature",
+        "shift the colorscheme warmer/cooler",
+        Value::Any,
+    ),
+    (
+        "--hue-rotate",
+        "rotate every color around the hue wheel",
+        Value::Any,
+    ),
+    ("--bias-hue", "pull hues toward this hue", Value::Any),
+    (
+        "--bias-strength",
+        "how far --bias-hue pulls (0-1)",
+        Value::Any,
+    ),
+    (
+        "--simulate",
+        "simulate color blindness in the preview",
+        Value::Choice(&["protanopia", "deuteranopia", "tritanopia"]),
+    ),
+    (
+        "--apply-simulation",
+        "apply the simulation to the colors",
+        Value::None,
+    ),
+    (
+        "--min-distance",
+        "nudge apart similar palette colors",
+        Value::Any,
+    ),
+    (
+        "--threshold-colors",
+        "replace similar palette colors",
+        Value::Any,
+    ),
+    (
+        "--sort-by",
+        "palette order",
+        Value::Choice(&["hue", "luminance", "saturation"]),
+    ),
+    (
+        "--pin-bg-fg",
+        "pick bg/fg before sorting the palette",
+        Value::None,
+    ),
+    (
+        "--pywal-order",
+        "order the colors the way pywal does",
+        Value::None,
+    ),
+    (
+        "--harmony",
+        "rebuild the palette with a color harmony",
+        Value::Choice(&["complementary", "triadic", "analogous"]),
+    ),
+    (
+        "--order-by-dominance",
+        "order the palette by pixel coverage",
+        Value::None,
+    ),
+    (
+        "--normalize-lightness",
+        "give every palette color the same lightness",
+        Value::None,
+    ),
+    (
+        "--target-lightness",
+        "oklch lightness for --normalize-lightness",
+        Value::Any,
+    ),
+];
+
+pub fn generate(shell: &str) -> Result<String, String> {
+    match shell {
+        "bash" => Ok(bash()),
+        "zsh" => Ok(zsh()),
+        "fish" => Ok(fish()),
+        _ => Err(format!("Unknown shell: {}", shell)),
+    }
+}
+
+fn bash() -> String {
+    let mut cases = Vec::new();
+
+    for (name, _, value) in FLAGS {
+        let reply = match value {
+            Value::None => continue,
+            Value::Any => String::new(),
+            Value::File => "COMPREPLY=($(compgen -f -- \"$cur\"))\n            ".to_string(),
+            Value::Choice(choices) => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            ",
+                choices.join(" ")
+            ),
+        };
+        cases.push(format!(
+            "        {name})\n            {reply}return\n            ;;"
+        ));
+    }
+
+    let flags = FLAGS
+        .iter()
+        .map(|(name, _, _)| *name)
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    format!(
+        r#"_rwal() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+{}
+    esac
+
+    COMPREPLY=($(compgen -W "{}" -- "$cur"))
+}}
+
+complete -F _rwal rwal
+"#,
+        cases.join("\n"),
+        flags
+    )
+}
+
+fn zsh() -> String {
+    let args = FLAGS
+        .iter()
+        .map(|(name, desc, value)| {
+            let action = match value {
+                Value::None => String::new(),
+                Value::Any => ":value: ".to_string(),
+                Value::File => ":path:_files".to_string(),
+                Value::Choice(choices) => format!(":value:({})", choices.join(" ")),
+            };
+            format!("    '{name}[{desc}]{action}'")
+        })
+        .collect::<Vec<String>>()
+        .join(" \\\n");
+
+    format!("#compdef rwal\n\n_arguments \\\n{}\n", args)
+}
+
+fn fish() -> String {
+    FLAGS
+        .iter()
+        .map(|(name, desc, value)| {
+            let option = match name.strip_prefix("--") {
+                Some(long) => format!("-l {long}"),
+                None => format!("-s {}", name.trim_start_matches('-')),
+            };
+            let action = match value {
+                Value::None => String::new(),
+                Value::Any => " -x".to_string(),
+                Value::File => " -r -F".to_string(),
+                Value::Choice(choices) => format!(" -x -a \"{}\"", choices.join(" ")),
+            };
+            format!("complete -c rwal {option} -d \"{desc}\"{action}\n")
+        })
+        .collect()
+}
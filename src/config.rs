@@ -1,29 +1,347 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
+use serde::Serialize;
 
 use crate::backends::Backend;
+use crate::harmony::Harmony;
+use crate::rwal::ClampMode;
+use crate::rwal::ColorStyle;
+use crate::rwal::FilterSpace;
+use crate::rwal::Format;
+use crate::rwal::HexCase;
+use crate::rwal::MixMode;
+use crate::rwal::Notation;
+use crate::rwal::Region;
+use crate::rwal::Simulation;
+use crate::rwal::SortBy;
+
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Preset {
+    Fast,
+    Quality,
+}
 
-#[derive(Debug, Deserialize)]
+/// One line description of every config field, written above it by `write_default`.
+const FIELD_DOCS: &[(&str, &str)] = &[
+    (
+        "version",
+        "config format version, older configs are migrated on load",
+    ),
+    (
+        "backend",
+        "palette backend (\"colorz\" | \"colorthief\" | \"histogram\")",
+    ),
+    (
+        "colorthief_quality",
+        "colorthief sampling step (1-10, 1 = slowest and most accurate)",
+    ),
+    (
+        "kmeans_runs",
+        "kmeans runs of the colorz backend, the best one is kept",
+    ),
+    (
+        "thumb_w",
+        "thumb width the image is resized to before sampling",
+    ),
+    (
+        "thumb_h",
+        "thumb height the image is resized to before sampling",
+    ),
+    (
+        "thumb_scale",
+        "thumb size relative to the image (0-100), overrides thumb_w/thumb_h",
+    ),
+    (
+        "max_pixels",
+        "thumb size in pixels keeping the aspect ratio, overrides the other thumb sizes",
+    ),
+    (
+        "preserve_aspect",
+        "fit the thumb within its size instead of stretching it",
+    ),
+    (
+        "respect_exif",
+        "rotate photos by their EXIF orientation before sampling",
+    ),
+    (
+        "page",
+        "page of a multi-page tiff or frame of an animated image to sample",
+    ),
+    (
+        "center_weight",
+        "favor pixels near the image center (0.0 - 1.0, 0 = uniform)",
+    ),
+    (
+        "edge_weight",
+        "favor detailed regions by their sobel gradient (0.0 - 1.0, 0 = uniform)",
+    ),
+    (
+        "sample_corners",
+        "only sample pixels near the edges of the thumb",
+    ),
+    (
+        "corner_margin",
+        "width of the sample_corners band in percent of the thumb size (1-50 with sample_corners)",
+    ),
+    ("crop", "crop the image to [x, y, w, h] before sampling"),
+    (
+        "crop_center",
+        "crop the image to its centered percentage (0-100)",
+    ),
+    ("bg_color", "background color"),
+    ("bg_idx", "palette color to mix with the background (0-7)"),
+    (
+        "bg_strength",
+        "amount of the palette color mixed into the background (0-100)",
+    ),
+    (
+        "bg_mode",
+        "how the background is derived (\"fixed\" | \"darken\" | \"lighten\")",
+    ),
+    (
+        "bg_from_corner",
+        "region of the image whose average color becomes the background",
+    ),
+    ("fg_color", "foreground color"),
+    ("fg_idx", "palette color to mix with the foreground (0-7)"),
+    (
+        "auto_bg_fg",
+        "mix the background with the darkest and the foreground with the lightest palette color",
+    ),
+    (
+        "fg_strength",
+        "amount of the palette color mixed into the foreground (0-100)",
+    ),
+    (
+        "fg_mode",
+        "how the foreground is derived (\"fixed\" | \"darken\" | \"lighten\")",
+    ),
+    (
+        "linear_mix",
+        "mix colors in linear light instead of raw sRGB",
+    ),
+    ("cursor_idx", "color used as the cursor (0-15)"),
+    ("cursor_color", "cursor color, overrides cursor_idx"),
+    ("light", "generate a light colorscheme"),
+    (
+        "light_map",
+        "palette indices color1-6 and the bright colors take in a light colorscheme",
+    ),
+    ("base_only", "only write color0-7"),
+    (
+        "base_theme",
+        "file whose 16 colors are kept except at merge_indices",
+    ),
+    (
+        "merge_indices",
+        "indices taken from the generated colors when using base_theme",
+    ),
+    (
+        "clamp_saturation",
+        "clamp saturation into clamp_saturation_min - clamp_saturation_max",
+    ),
+    (
+        "clamp_value",
+        "clamp value into clamp_value_min - clamp_value_max",
+    ),
+    (
+        "skip_saturation",
+        "keep only colors inside the saturation skip range",
+    ),
+    ("skip_value", "keep only colors inside the value skip range"),
+    ("skip_inclusive", "keep colors exactly at the skip bounds"),
+    (
+        "skip_invert",
+        "remove the colors inside the skip ranges instead",
+    ),
+    (
+        "filter_space",
+        "space the skip/clamp bounds apply in (\"hsv\" | \"hsl\" | \"lab\")",
+    ),
+    (
+        "clamp_mode",
+        "\"hard\" clamps into the range, \"soft\" rescales the whole range into it",
+    ),
+    ("clamp_value_min", "min value clamp (0.0 - 1.0)"),
+    ("clamp_value_max", "max value clamp (0.0 - 1.0)"),
+    ("clamp_saturation_min", "min saturation clamp (0.0 - 1.0)"),
+    ("clamp_saturation_max", "max saturation clamp (0.0 - 1.0)"),
+    ("skip_value_min", "min value skip (0.0 - 1.0)"),
+    ("skip_value_max", "max value skip (0.0 - 1.0)"),
+    ("skip_saturation_min", "min saturation skip (0.0 - 1.0)"),
+    ("skip_saturation_max", "max saturation skip (0.0 - 1.0)"),
+    ("exclude_extremes", "drop near-black and near-white pixels"),
+    (
+        "exclude_dark",
+        "drop pixels with a value below this (0.0 - 1.0)",
+    ),
+    (
+        "exclude_light",
+        "drop pixels with a whiteness above this (0.0 - 1.0)",
+    ),
+    (
+        "simulate",
+        "simulate color blindness in the preview (\"protanopia\" | \"deuteranopia\" | \"tritanopia\")",
+    ),
+    (
+        "apply_simulation",
+        "also apply the simulation to the written colors",
+    ),
+    (
+        "min_distance",
+        "nudge apart palette colors closer than this CIELAB delta E",
+    ),
+    (
+        "threshold_colors",
+        "replace palette colors closer than this CIELAB delta E with the next distinct cluster",
+    ),
+    (
+        "sort_by",
+        "palette order (\"hue\" | \"luminance\" | \"saturation\")",
+    ),
+    (
+        "order_by_dominance",
+        "order the palette by how many pixels each color covers",
+    ),
+    (
+        "normalize_lightness",
+        "set every palette color to target_lightness",
+    ),
+    (
+        "target_lightness",
+        "oklch lightness for normalize_lightness (0.0 - 1.0)",
+    ),
+    (
+        "harmony",
+        "rebuild the palette from the dominant colors (\"complementary\" | \"triadic\" | \"analogous\")",
+    ),
+    ("pin_bg_fg", "pick the bg/fg palette colors before sorting"),
+    (
+        "pywal_order",
+        "reorder the final colors the way pywal templates expect",
+    ),
+    (
+        "passthrough",
+        "use the image colors as is when there are at most 16 of them, keeping the first 8",
+    ),
+    (
+        "format",
+        "format of -o (\"hex\" | \"labeled\" | \"xresources\" | \"windows-terminal\" | \"base16\" | \"base24\")",
+    ),
+    (
+        "hex_case",
+        "case of hex colors written by -o (\"lower\" | \"upper\")",
+    ),
+    (
+        "color_notation",
+        "notation of colors written by -o (\"hex\" | \"rgb\")",
+    ),
+    (
+        "post_hook",
+        "shell command run after the colors are written",
+    ),
+    ("write_preview", "write preview.html"),
+    ("timeout", "seconds before falling back to colorthief"),
+    (
+        "max_retries",
+        "rerun the backend with new seeds until it yields 8 distinct colors",
+    ),
+    (
+        "phash_threshold",
+        "reuse the cache of images whose perceptual hashes differ in at most this many bits (0 = off)",
+    ),
+    ("seed", "seed for the backend and random sampling"),
+    ("sample_stride", "only sample every nth pixel of the thumb"),
+    (
+        "sample_random",
+        "only sample this many random pixels of the thumb",
+    ),
+    (
+        "temperature",
+        "shift the colorscheme warmer/cooler in kelvin (1000-40000, 6500 = none)",
+    ),
+    (
+        "hue_rotate",
+        "rotate every color around the hue wheel in degrees",
+    ),
+    (
+        "bias_hue",
+        "pull every color's hue toward this hue in degrees",
+    ),
+    ("bias_strength", "how far bias_hue pulls (0.0 - 1.0)"),
+    (
+        "presets",
+        "named tables applied over this config with --preset",
+    ),
+];
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Config {
+    pub version: u32,
+
     pub backend: Backend,
+    pub colorthief_quality: u8,
+    pub kmeans_runs: u32,
     pub thumb_w: u32,
     pub thumb_h: u32,
+    pub thumb_scale: Option<f32>,
+    pub max_pixels: Option<u32>,
+    pub preserve_aspect: bool,
+    pub respect_exif: bool,
+    pub page: usize,
+    pub center_weight: f32,
+    pub edge_weight: f32,
+    pub sample_corners: bool,
+    pub corner_margin: f32,
+    pub crop: Option<(u32, u32, u32, u32)>,
+    pub crop_center: Option<f32>,
 
-    #[serde(deserialize_with = "deserialize_hex_color")]
+    #[serde(
+        deserialize_with = "deserialize_hex_color",
+        serialize_with = "serialize_hex_color"
+    )]
     pub bg_color: (u8, u8, u8),
     pub bg_idx: usize,
     pub bg_strength: u8,
+    pub bg_mode: MixMode,
+    pub bg_from_corner: Option<Region>,
 
-    #[serde(deserialize_with = "deserialize_hex_color")]
+    #[serde(
+        deserialize_with = "deserialize_hex_color",
+        serialize_with = "serialize_hex_color"
+    )]
     pub fg_color: (u8, u8, u8),
     pub fg_idx: usize,
+    pub auto_bg_fg: bool,
     pub fg_strength: u8,
+    pub fg_mode: MixMode,
+    pub linear_mix: bool,
+
+    pub cursor_idx: usize,
+    #[serde(
+        deserialize_with = "deserialize_opt_hex_color",
+        serialize_with = "serialize_opt_hex_color"
+    )]
+    pub cursor_color: Option<(u8, u8, u8)>,
 
     pub light: bool,
+    pub light_map: [usize; 8],
+    pub base_only: bool,
+    pub base_theme: Option<String>,
+    pub merge_indices: Vec<usize>,
 
     pub clamp_saturation: bool,
     pub clamp_value: bool,
     pub skip_saturation: bool,
     pub skip_value: bool,
+    pub skip_inclusive: bool,
+    pub skip_invert: bool,
+    pub filter_space: FilterSpace,
+    pub clamp_mode: ClampMode,
 
     pub clamp_value_min: f32,
     pub clamp_value_max: f32,
@@ -36,6 +354,41 @@ pub struct Config {
 
     pub skip_saturation_min: f32,
     pub skip_saturation_max: f32,
+
+    pub exclude_extremes: bool,
+    pub exclude_dark: f32,
+    pub exclude_light: f32,
+
+    pub simulate: Option<Simulation>,
+    pub apply_simulation: bool,
+
+    pub min_distance: Option<f32>,
+    pub threshold_colors: Option<f32>,
+    pub sort_by: SortBy,
+    pub order_by_dominance: bool,
+    pub normalize_lightness: bool,
+    pub target_lightness: f32,
+    pub harmony: Option<Harmony>,
+    pub pin_bg_fg: bool,
+    pub pywal_order: bool,
+    pub passthrough: bool,
+    pub format: Format,
+    pub hex_case: HexCase,
+    pub color_notation: Notation,
+    pub post_hook: Option<String>,
+    pub write_preview: bool,
+    pub timeout: Option<u64>,
+    pub max_retries: u32,
+    pub phash_threshold: u32,
+    pub seed: u64,
+    pub sample_stride: u32,
+    pub sample_random: Option<u32>,
+    pub temperature: Option<f32>,
+    pub hue_rotate: f32,
+    pub bias_hue: Option<f32>,
+    pub bias_strength: f32,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub presets: HashMap<String, toml::Table>,
 }
 
 impl Config {
@@ -43,43 +396,333 @@ impl Config {
         path: P,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         log::info!("Reading config");
-        let contents = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&contents)?;
+        let path = path.as_ref();
+        let system = crate::dirs::SYSTEM_CONFIG_FILE.as_path();
+
+        let mut table = toml::Table::new();
+        let mut layers = Vec::new();
+
+        if path != system && system.exists() {
+            merge(&mut table, read_table(system)?);
+            layers.push(system.display().to_string());
+        }
+
+        match read_table(path) {
+            Ok(user) => {
+                merge(&mut table, user);
+                layers.push(path.display().to_string());
+            }
+            Err(e)
+                if !layers.is_empty()
+                    && e.downcast_ref::<std::io::Error>()
+                        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                log::info!("No config at {}", path.display());
+            }
+            Err(e) => return Err(e),
+        }
+
+        log::info!("Config layers: {}", layers.join(", "));
+
+        Self::from_table(table)
+    }
+
+    pub fn from_stdin() -> Result<Self, Box<dyn std::error::Error>> {
+        log::info!("Reading config from stdin");
+        let contents = std::io::read_to_string(std::io::stdin())?;
+
+        Self::from_table(toml::from_str(&contents)?)
+    }
+
+    fn from_table(table: toml::Table) -> Result<Self, Box<dyn std::error::Error>> {
+        let keys = table.keys().cloned().collect::<Vec<String>>();
+        let mut config: Config = toml::Value::Table(table).try_into()?;
+
+        let defaulted = config
+            .fields()
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| !keys.iter().any(|k| k == name))
+            .collect::<Vec<&str>>();
 
+        if !defaulted.is_empty() {
+            log::info!("Using default values for {}", defaulted.join(", "));
+        }
+
+        if !keys.iter().any(|k| k == "version") {
+            config.version = 0;
+        }
+
+        config.migrate()?;
         config.validate()?;
 
         Ok(config)
     }
 
+    pub fn to_toml(&self) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn write_default<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            return Err(format!("{} already exists", path.display()).into());
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, Config::default().to_commented_toml()?)?;
+
+        Ok(())
+    }
+
+    fn to_commented_toml(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let toml = self.to_toml()?;
+        let mut contents = String::from("# rwal config, every value below is the default\n");
+
+        for (name, doc) in FIELD_DOCS {
+            contents.push_str(&format!("\n# {}\n", doc));
+            match toml
+                .lines()
+                .find(|line| line.split_once(" = ").is_some_and(|(key, _)| key == *name))
+            {
+                Some(line) => contents.push_str(&format!("{}\n", line)),
+                None => contents.push_str(&format!("# {} is unset by default\n", name)),
+            }
+        }
+
+        Ok(contents)
+    }
+
+    fn migrate(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.version > CONFIG_VERSION {
+            return Err(format!(
+                "config version {} is newer than supported version {}",
+                self.version, CONFIG_VERSION
+            )
+            .into());
+        }
+
+        if self.version < CONFIG_VERSION {
+            log::info!(
+                "Migrating config from version {} to {}",
+                self.version,
+                CONFIG_VERSION
+            );
+            self.version = CONFIG_VERSION;
+        }
+
+        Ok(())
+    }
+
+    pub fn color_style(&self) -> ColorStyle {
+        ColorStyle {
+            notation: self.color_notation,
+            hex_case: self.hex_case,
+        }
+    }
+
+    pub fn apply_named_preset(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let overlay = self
+            .presets
+            .get(name)
+            .ok_or_else(|| format!("Unknown preset: {}", name))?
+            .clone();
+
+        log::info!("Applying preset {}", name);
+
+        let presets = std::mem::take(&mut self.presets);
+        let mut table = match toml::Value::try_from(&*self)? {
+            toml::Value::Table(table) => table,
+            _ => unreachable!(),
+        };
+        merge(&mut table, overlay);
+
+        *self = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("Invalid preset {}: {}", name, e))?;
+        self.presets = presets;
+
+        self.validate()
+    }
+
+    pub fn apply_preset(&mut self, preset: Preset) {
+        match preset {
+            Preset::Fast => {
+                self.backend = Backend::Colorthief;
+                self.thumb_w = 50;
+                self.thumb_h = 50;
+                self.thumb_scale = None;
+                self.max_pixels = None;
+                self.clamp_saturation = false;
+                self.clamp_value = false;
+                self.skip_saturation = false;
+                self.skip_value = false;
+                self.kmeans_runs = 1;
+                self.max_retries = 0;
+            }
+            Preset::Quality => {
+                self.backend = Backend::ColorZ;
+                self.thumb_w = 200;
+                self.thumb_h = 200;
+                self.thumb_scale = None;
+                self.max_pixels = None;
+                self.colorthief_quality = 1;
+                self.kmeans_runs = 10;
+                self.max_retries = 3;
+                self.sample_stride = 1;
+                self.sample_random = None;
+            }
+        }
+    }
+
     pub fn cache_string(&self) -> String {
-        format!(
-            "{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
-            self.backend.to_string(),
-            self.thumb_w,
-            self.thumb_h,
-            rgb_to_hex(self.bg_color),
-            self.bg_idx,
-            self.bg_strength,
-            rgb_to_hex(self.fg_color),
-            self.fg_idx,
-            self.fg_strength,
-            self.light,
-            self.clamp_saturation,
-            self.clamp_value,
-            self.skip_saturation,
-            self.skip_value,
-            self.clamp_value_min,
-            self.clamp_value_max,
-            self.clamp_saturation_min,
-            self.clamp_saturation_max,
-            self.skip_value_min,
-            self.skip_value_max,
-            self.skip_saturation_min,
-            self.skip_saturation_max,
-        )
+        self.fields()
+            .into_iter()
+            .filter(|(name, _)| !Self::UNCACHED.contains(name))
+            .map(|(name, value)| match (name, &self.base_theme) {
+                ("base_theme", Some(path)) => match std::fs::read(path) {
+                    Ok(contents) => format!("{}@{:016x}", value, fnv1a(&contents)),
+                    Err(_) => value,
+                },
+                _ => value,
+            })
+            .collect::<Vec<String>>()
+            .join("_")
+    }
+
+    const UNCACHED: &[&str] = &[
+        "version",
+        "timeout",
+        "format",
+        "hex_case",
+        "color_notation",
+        "post_hook",
+        "write_preview",
+        "phash_threshold",
+        "presets",
+    ];
+
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("version", self.version.to_string()),
+            ("backend", self.backend.to_string()),
+            ("colorthief_quality", self.colorthief_quality.to_string()),
+            ("kmeans_runs", self.kmeans_runs.to_string()),
+            ("thumb_w", self.thumb_w.to_string()),
+            ("thumb_h", self.thumb_h.to_string()),
+            ("thumb_scale", opt(self.thumb_scale)),
+            ("max_pixels", opt(self.max_pixels)),
+            ("preserve_aspect", self.preserve_aspect.to_string()),
+            ("respect_exif", self.respect_exif.to_string()),
+            ("page", self.page.to_string()),
+            ("center_weight", self.center_weight.to_string()),
+            ("edge_weight", self.edge_weight.to_string()),
+            ("sample_corners", self.sample_corners.to_string()),
+            ("corner_margin", self.corner_margin.to_string()),
+            (
+                "crop",
+                opt(self.crop.map(|(x, y, w, h)| format!("{x},{y},{w},{h}"))),
+            ),
+            ("crop_center", opt(self.crop_center)),
+            ("bg_color", rgb_to_hex(self.bg_color)),
+            ("bg_idx", self.bg_idx.to_string()),
+            ("bg_strength", self.bg_strength.to_string()),
+            ("bg_mode", self.bg_mode.to_string()),
+            ("bg_from_corner", opt(self.bg_from_corner)),
+            ("fg_color", rgb_to_hex(self.fg_color)),
+            ("fg_idx", self.fg_idx.to_string()),
+            ("auto_bg_fg", self.auto_bg_fg.to_string()),
+            ("fg_strength", self.fg_strength.to_string()),
+            ("fg_mode", self.fg_mode.to_string()),
+            ("linear_mix", self.linear_mix.to_string()),
+            ("cursor_idx", self.cursor_idx.to_string()),
+            ("cursor_color", opt(self.cursor_color.map(rgb_to_hex))),
+            ("light", self.light.to_string()),
+            (
+                "light_map",
+                self.light_map
+                    .iter()
+                    .map(|idx| idx.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            ("base_only", self.base_only.to_string()),
+            ("base_theme", opt(self.base_theme.as_ref())),
+            (
+                "merge_indices",
+                self.merge_indices
+                    .iter()
+                    .map(|idx| idx.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            ("clamp_saturation", self.clamp_saturation.to_string()),
+            ("clamp_value", self.clamp_value.to_string()),
+            ("skip_saturation", self.skip_saturation.to_string()),
+            ("skip_value", self.skip_value.to_string()),
+            ("skip_inclusive", self.skip_inclusive.to_string()),
+            ("skip_invert", self.skip_invert.to_string()),
+            ("filter_space", self.filter_space.to_string()),
+            ("clamp_mode", self.clamp_mode.to_string()),
+            ("clamp_value_min", self.clamp_value_min.to_string()),
+            ("clamp_value_max", self.clamp_value_max.to_string()),
+            (
+                "clamp_saturation_min",
+                self.clamp_saturation_min.to_string(),
+            ),
+            (
+                "clamp_saturation_max",
+                self.clamp_saturation_max.to_string(),
+            ),
+            ("skip_value_min", self.skip_value_min.to_string()),
+            ("skip_value_max", self.skip_value_max.to_string()),
+            ("skip_saturation_min", self.skip_saturation_min.to_string()),
+            ("skip_saturation_max", self.skip_saturation_max.to_string()),
+            ("exclude_extremes", self.exclude_extremes.to_string()),
+            ("exclude_dark", self.exclude_dark.to_string()),
+            ("exclude_light", self.exclude_light.to_string()),
+            ("simulate", opt(self.simulate)),
+            ("apply_simulation", self.apply_simulation.to_string()),
+            ("min_distance", opt(self.min_distance)),
+            ("threshold_colors", opt(self.threshold_colors)),
+            ("sort_by", self.sort_by.to_string()),
+            ("order_by_dominance", self.order_by_dominance.to_string()),
+            ("normalize_lightness", self.normalize_lightness.to_string()),
+            ("target_lightness", self.target_lightness.to_string()),
+            ("harmony", opt(self.harmony)),
+            ("pin_bg_fg", self.pin_bg_fg.to_string()),
+            ("pywal_order", self.pywal_order.to_string()),
+            ("passthrough", self.passthrough.to_string()),
+            ("format", self.format.to_string()),
+            ("hex_case", self.hex_case.to_string()),
+            ("color_notation", self.color_notation.to_string()),
+            ("post_hook", self.post_hook.clone().unwrap_or_default()),
+            ("write_preview", self.write_preview.to_string()),
+            ("timeout", opt(self.timeout)),
+            ("max_retries", self.max_retries.to_string()),
+            ("phash_threshold", self.phash_threshold.to_string()),
+            ("seed", self.seed.to_string()),
+            ("sample_stride", self.sample_stride.to_string()),
+            ("sample_random", opt(self.sample_random)),
+            ("temperature", opt(self.temperature)),
+            ("hue_rotate", self.hue_rotate.to_string()),
+            ("bias_hue", opt(self.bias_hue)),
+            ("bias_strength", self.bias_strength.to_string()),
+            ("presets", {
+                let mut names = self.presets.keys().cloned().collect::<Vec<String>>();
+                names.sort();
+                names.join(",")
+            }),
+        ]
     }
 
-    fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Validating config");
         if self.thumb_w < 1 {
             return Err("thumb_w must be at least 1".into());
@@ -88,6 +731,46 @@ impl Config {
             return Err("thumb_h must be at least 1".into());
         }
 
+        if self.max_pixels == Some(0) {
+            return Err("max_pixels must be at least 1".into());
+        }
+
+        if !(1..=10).contains(&self.colorthief_quality) {
+            return Err("colorthief_quality must be between 1 and 10".into());
+        }
+
+        if self.kmeans_runs < 1 {
+            return Err("kmeans_runs must be at least 1".into());
+        }
+
+        if self.phash_threshold > 64 {
+            return Err("phash_threshold must be between 0 and 64".into());
+        }
+
+        if self.sample_stride < 1 {
+            return Err("sample_stride must be at least 1".into());
+        }
+        if self.sample_random == Some(0) {
+            return Err("sample_random must be at least 1".into());
+        }
+
+        if let Some(thumb_scale) = self.thumb_scale
+            && !(thumb_scale > 0.0 && thumb_scale <= 100.0)
+        {
+            return Err("thumb_scale must be between 0 and 100".into());
+        }
+
+        if let Some((_, _, w, h)) = self.crop
+            && (w < 1 || h < 1)
+        {
+            return Err("crop width and height must be at least 1".into());
+        }
+        if let Some(crop_center) = self.crop_center
+            && !(crop_center > 0.0 && crop_center <= 100.0)
+        {
+            return Err("crop_center must be between 0 and 100".into());
+        }
+
         if self.bg_idx > 7 {
             return Err("bg_idx must be between 1 and 7".into());
         }
@@ -95,6 +778,18 @@ impl Config {
             return Err("fg_idx must be between 1 and 7".into());
         }
 
+        if self.cursor_idx > 15 {
+            return Err("cursor_idx must be between 0 and 15".into());
+        }
+
+        if self.merge_indices.iter().any(|&idx| idx > 15) {
+            return Err("merge_indices must be between 0 and 15".into());
+        }
+
+        if self.light_map.iter().any(|&idx| idx > 7) {
+            return Err("light_map indices must be between 0 and 7".into());
+        }
+
         if self.bg_strength > 100 {
             return Err("bg_strength must be between 0 and 100".into());
         }
@@ -102,6 +797,21 @@ impl Config {
             return Err("fg_strength must be between 0 and 100".into());
         }
 
+        if !(0.0..=1.0).contains(&self.center_weight) {
+            return Err("center_weight must be between 0.0 and 1.0".into());
+        }
+
+        if !(0.0..=1.0).contains(&self.edge_weight) {
+            return Err("edge_weight must be between 0.0 and 1.0".into());
+        }
+
+        if !(0.0..=50.0).contains(&self.corner_margin) {
+            return Err("corner_margin must be between 0 and 50".into());
+        }
+        if self.sample_corners && self.corner_margin < 1.0 {
+            return Err("corner_margin must be at least 1 with sample_corners".into());
+        }
+
         let float_validations = [
             ("clamp_value_min", self.clamp_value_min),
             ("clamp_value_max", self.clamp_value_max),
@@ -111,14 +821,42 @@ impl Config {
             ("skip_value_max", self.skip_value_max),
             ("skip_saturation_min", self.skip_saturation_min),
             ("skip_saturation_max", self.skip_saturation_max),
+            ("exclude_dark", self.exclude_dark),
+            ("exclude_light", self.exclude_light),
         ];
 
         for (name, value) in float_validations {
-            if !(0.0..1.0).contains(&value) {
+            if !(0.0..=1.0).contains(&value) {
                 return Err(format!("{} must be between 0.0 and 1.0", name).into());
             }
         }
 
+        if let Some(min_distance) = self.min_distance
+            && min_distance < 0.0
+        {
+            return Err("min_distance must be positive".into());
+        }
+
+        if !(0.0..=1.0).contains(&self.target_lightness) {
+            return Err("target_lightness must be between 0.0 and 1.0".into());
+        }
+
+        if let Some(threshold_colors) = self.threshold_colors
+            && threshold_colors < 0.0
+        {
+            return Err("threshold_colors must be positive".into());
+        }
+
+        if !(0.0..=1.0).contains(&self.bias_strength) {
+            return Err("bias_strength must be between 0 and 1".into());
+        }
+
+        if let Some(temperature) = self.temperature
+            && !(1000.0..=40000.0).contains(&temperature)
+        {
+            return Err("temperature must be between 1000 and 40000".into());
+        }
+
         if self.clamp_value_min > self.clamp_value_max {
             return Err("clamp_value_min must be <= clamp_value_max".into());
         }
@@ -132,6 +870,16 @@ impl Config {
             return Err("skip_saturation_min must be <= skip_saturation_max".into());
         }
 
+        let fields = self.fields();
+        for (name, preset) in &self.presets {
+            if let Some(key) = preset
+                .keys()
+                .find(|key| key.as_str() == "presets" || !fields.iter().any(|(f, _)| f == key))
+            {
+                return Err(format!("preset {} has unknown key {}", name, key).into());
+            }
+        }
+
         Ok(())
     }
 }
@@ -139,20 +887,49 @@ impl Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             backend: Backend::ColorZ,
+            colorthief_quality: 5,
+            kmeans_runs: 3,
             thumb_w: 100,
             thumb_h: 100,
+            thumb_scale: None,
+            max_pixels: None,
+            preserve_aspect: false,
+            respect_exif: false,
+            page: 0,
+            center_weight: 0.0,
+            edge_weight: 0.0,
+            sample_corners: false,
+            corner_margin: 10.0,
+            crop: None,
+            crop_center: None,
             bg_color: (0, 0, 0),
             bg_idx: 0,
             bg_strength: 10,
+            bg_mode: MixMode::Fixed,
+            bg_from_corner: None,
             fg_color: (255, 255, 255),
             fg_idx: 0,
+            auto_bg_fg: false,
             fg_strength: 10,
+            fg_mode: MixMode::Fixed,
+            linear_mix: false,
+            cursor_idx: 7,
+            cursor_color: None,
             light: false,
+            light_map: [0, 1, 2, 3, 4, 5, 6, 7],
+            base_only: false,
+            base_theme: None,
+            merge_indices: vec![1, 2, 3, 4, 5, 6],
             clamp_saturation: true,
             clamp_value: true,
             skip_saturation: true,
             skip_value: false,
+            skip_inclusive: false,
+            skip_invert: false,
+            filter_space: FilterSpace::Hsv,
+            clamp_mode: ClampMode::Hard,
             clamp_value_min: 0.4,
             clamp_value_max: 0.5,
             clamp_saturation_min: 0.4,
@@ -161,10 +938,70 @@ impl Default for Config {
             skip_value_max: 0.9,
             skip_saturation_min: 0.3,
             skip_saturation_max: 0.7,
+            exclude_extremes: false,
+            exclude_dark: 0.05,
+            exclude_light: 0.95,
+            simulate: None,
+            apply_simulation: false,
+            min_distance: None,
+            threshold_colors: None,
+            sort_by: SortBy::Hue,
+            order_by_dominance: false,
+            normalize_lightness: false,
+            target_lightness: 0.7,
+            harmony: None,
+            pin_bg_fg: false,
+            pywal_order: false,
+            passthrough: false,
+            format: Format::Hex,
+            hex_case: HexCase::Lower,
+            color_notation: Notation::Hex,
+            post_hook: None,
+            write_preview: true,
+            timeout: None,
+            max_retries: 0,
+            phash_threshold: 0,
+            seed: 0,
+            sample_stride: 1,
+            sample_random: None,
+            temperature: None,
+            hue_rotate: 0.0,
+            bias_hue: None,
+            bias_strength: 0.5,
+            presets: HashMap::new(),
+        }
+    }
+}
+
+fn read_table(path: &std::path::Path) -> Result<toml::Table, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => {
+            let mut table: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(&contents)?;
+            table.retain(|_, v| !v.is_null());
+            Ok(toml::Table::try_from(table)?)
         }
+        _ => Ok(toml::from_str(&contents)?),
     }
 }
 
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
 fn deserialize_hex_color<'de, D>(deserializer: D) -> Result<(u8, u8, u8), D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -173,17 +1010,94 @@ where
     hex_to_rgb(&s).map_err(serde::de::Error::custom)
 }
 
+fn serialize_hex_color<S>(color: &(u8, u8, u8), serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&rgb_to_hex(*color))
+}
+
+fn serialize_opt_hex_color<S>(
+    color: &Option<(u8, u8, u8)>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match color {
+        Some(color) => serializer.serialize_some(&rgb_to_hex(*color)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_opt_hex_color<'de, D>(deserializer: D) -> Result<Option<(u8, u8, u8)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    s.map(|s| hex_to_rgb(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+const NAMED_COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("black", (0, 0, 0)),
+    ("white", (255, 255, 255)),
+    ("red", (255, 0, 0)),
+    ("green", (0, 128, 0)),
+    ("lime", (0, 255, 0)),
+    ("blue", (0, 0, 255)),
+    ("yellow", (255, 255, 0)),
+    ("cyan", (0, 255, 255)),
+    ("magenta", (255, 0, 255)),
+    ("gray", (128, 128, 128)),
+    ("grey", (128, 128, 128)),
+    ("silver", (192, 192, 192)),
+    ("maroon", (128, 0, 0)),
+    ("olive", (128, 128, 0)),
+    ("navy", (0, 0, 128)),
+    ("purple", (128, 0, 128)),
+    ("teal", (0, 128, 128)),
+    ("orange", (255, 165, 0)),
+    ("pink", (255, 192, 203)),
+    ("brown", (165, 42, 42)),
+];
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 pub fn hex_to_rgb(hex: &str) -> Result<(u8, u8, u8), String> {
-    if !hex.starts_with('#') || hex.len() != 7 {
-        return Err(format!("Invalid hex color format: {}", hex));
+    if !hex.starts_with('#') {
+        return NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(hex))
+            .map(|&(_, rgb)| rgb)
+            .ok_or(format!("Unknown color: {}", hex));
     }
 
-    let r =
-        u8::from_str_radix(&hex[1..3], 16).map_err(|e| format!("Invalid red component: {}", e))?;
-    let g = u8::from_str_radix(&hex[3..5], 16)
+    let Some(digits) = hex.strip_prefix('#').filter(|digits| digits.is_ascii()) else {
+        return Err(format!("Invalid hex color format: {}", hex));
+    };
+
+    let digits = match digits.len() {
+        3 => digits.chars().flat_map(|c| [c, c]).collect::<String>(),
+        6 => digits.to_string(),
+        8 => {
+            u8::from_str_radix(&digits[6..8], 16)
+                .map_err(|e| format!("Invalid alpha component: {}", e))?;
+            digits[..6].to_string()
+        }
+        _ => return Err(format!("Invalid hex color format: {}", hex)),
+    };
+
+    let r = u8::from_str_radix(&digits[0..2], 16)
+        .map_err(|e| format!("Invalid red component: {}", e))?;
+    let g = u8::from_str_radix(&digits[2..4], 16)
         .map_err(|e| format!("Invalid green component: {}", e))?;
-    let b =
-        u8::from_str_radix(&hex[5..7], 16).map_err(|e| format!("Invalid blue component: {}", e))?;
+    let b = u8::from_str_radix(&digits[4..6], 16)
+        .map_err(|e| format!("Invalid blue component: {}", e))?;
 
     Ok((r, g, b))
 }
@@ -191,3 +1105,286 @@ pub fn hex_to_rgb(hex: &str) -> Result<(u8, u8, u8), String> {
 pub fn rgb_to_hex(rgb: (u8, u8, u8)) -> String {
     format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2)
 }
+
+pub fn rgb_to_css(rgb: (u8, u8, u8)) -> String {
+    format!("rgb({}, {}, {})", rgb.0, rgb.1, rgb.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_config_defaults_the_rest() {
+        let table: toml::Table = toml::from_str("backend = \"colorthief\"").unwrap();
+        let config = Config::from_table(table).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                backend: Backend::Colorthief,
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn old_config_is_migrated() {
+        let table: toml::Table = toml::from_str("thumb_w = 50").unwrap();
+        let config = Config::from_table(table).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.thumb_w, 50);
+    }
+
+    #[test]
+    fn newer_config_is_rejected() {
+        let table: toml::Table =
+            toml::from_str(&format!("version = {}", CONFIG_VERSION + 1)).unwrap();
+        assert!(Config::from_table(table).is_err());
+    }
+
+    #[test]
+    fn json_and_toml_configs_match() {
+        let dir = std::env::temp_dir().join(format!("rwal-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("config.toml");
+        std::fs::write(
+            &toml_path,
+            "backend = \"histogram\"\nthumb_w = 64\nbg_color = \"#101010\"\ncrop = [1, 2, 3, 4]\n",
+        )
+        .unwrap();
+        let json_path = dir.join("config.json");
+        std::fs::write(
+            &json_path,
+            r##"{"backend": "histogram", "thumb_w": 64, "bg_color": "#101010", "crop": [1, 2, 3, 4], "timeout": null}"##,
+        )
+        .unwrap();
+
+        let from_toml = Config::from_table(read_table(&toml_path).unwrap()).unwrap();
+        let from_json = Config::from_table(read_table(&json_path).unwrap()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(from_toml, from_json);
+        assert_eq!(from_json.backend, Backend::Histogram);
+        assert_eq!(from_json.crop, Some((1, 2, 3, 4)));
+    }
+
+    #[test]
+    fn every_cached_field_changes_the_cache_key() {
+        macro_rules! changed {
+            ($($field:ident: $value:expr),* $(,)?) => {
+                vec![$((stringify!($field), Config { $field: $value, ..Config::default() })),*]
+            };
+        }
+
+        let changed = changed! {
+            backend: Backend::Histogram,
+            colorthief_quality: 1,
+            kmeans_runs: 1,
+            thumb_w: 50,
+            thumb_h: 50,
+            thumb_scale: Some(50.0),
+            max_pixels: Some(1000),
+            preserve_aspect: true,
+            respect_exif: true,
+            page: 1,
+            center_weight: 0.5,
+            edge_weight: 0.5,
+            sample_corners: true,
+            corner_margin: 20.0,
+            crop: Some((0, 0, 10, 10)),
+            crop_center: Some(50.0),
+            bg_color: (1, 2, 3),
+            bg_idx: 1,
+            bg_strength: 20,
+            bg_mode: MixMode::Darken,
+            bg_from_corner: Some(Region::Center),
+            fg_color: (1, 2, 3),
+            fg_idx: 1,
+            auto_bg_fg: true,
+            fg_strength: 20,
+            fg_mode: MixMode::Lighten,
+            linear_mix: true,
+            cursor_idx: 1,
+            cursor_color: Some((1, 2, 3)),
+            light: true,
+            light_map: [7, 6, 5, 4, 3, 2, 1, 0],
+            base_only: true,
+            base_theme: Some("base".into()),
+            merge_indices: vec![1],
+            clamp_saturation: false,
+            clamp_value: false,
+            skip_saturation: false,
+            skip_value: true,
+            skip_inclusive: true,
+            skip_invert: true,
+            filter_space: FilterSpace::Lab,
+            clamp_mode: ClampMode::Soft,
+            clamp_value_min: 0.1,
+            clamp_value_max: 0.9,
+            clamp_saturation_min: 0.1,
+            clamp_saturation_max: 0.9,
+            skip_value_min: 0.2,
+            skip_value_max: 0.8,
+            skip_saturation_min: 0.2,
+            skip_saturation_max: 0.8,
+            exclude_extremes: true,
+            exclude_dark: 0.1,
+            exclude_light: 0.9,
+            simulate: Some(Simulation::Protanopia),
+            apply_simulation: true,
+            min_distance: Some(5.0),
+            threshold_colors: Some(5.0),
+            sort_by: SortBy::Luminance,
+            order_by_dominance: true,
+            normalize_lightness: true,
+            target_lightness: 0.5,
+            harmony: Some(Harmony::Triadic),
+            pin_bg_fg: true,
+            pywal_order: true,
+            passthrough: true,
+            max_retries: 3,
+            seed: 1,
+            sample_stride: 2,
+            sample_random: Some(100),
+            temperature: Some(3000.0),
+            hue_rotate: 90.0,
+            bias_hue: Some(90.0),
+            bias_strength: 1.0,
+        };
+
+        let default = Config::default().cache_string();
+        for (name, config) in &changed {
+            assert_ne!(
+                config.cache_string(),
+                default,
+                "{} is not in the cache key",
+                name
+            );
+        }
+
+        let cached = Config::default()
+            .fields()
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| !Config::UNCACHED.contains(name))
+            .collect::<Vec<_>>();
+        let covered = changed.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(cached, covered);
+    }
+
+    #[test]
+    fn hex_color_lengths() {
+        assert_eq!(hex_to_rgb("#fff"), Ok((255, 255, 255)));
+        assert_eq!(hex_to_rgb("#1a2"), Ok((0x11, 0xaa, 0x22)));
+        assert_eq!(hex_to_rgb("#ffffff"), Ok((255, 255, 255)));
+        assert_eq!(hex_to_rgb("#102030"), Ok((0x10, 0x20, 0x30)));
+        assert_eq!(hex_to_rgb("#ffffffff"), Ok((255, 255, 255)));
+        assert_eq!(hex_to_rgb("#10203040"), Ok((0x10, 0x20, 0x30)));
+    }
+
+    #[test]
+    fn malformed_hex_colors() {
+        for hex in [
+            "#",
+            "#ff",
+            "#ffff",
+            "#fffff",
+            "#fffffff",
+            "#ggg",
+            "#10203g",
+            "#102030zz",
+            "#ééé",
+        ] {
+            assert!(hex_to_rgb(hex).is_err(), "{} parsed", hex);
+        }
+    }
+
+    #[test]
+    fn named_colors() {
+        for (name, rgb) in [
+            ("black", (0, 0, 0)),
+            ("white", (255, 255, 255)),
+            ("White", (255, 255, 255)),
+            ("RED", (255, 0, 0)),
+            ("green", (0, 128, 0)),
+            ("Grey", (128, 128, 128)),
+            ("brown", (165, 42, 42)),
+        ] {
+            assert_eq!(hex_to_rgb(name), Ok(rgb), "{}", name);
+        }
+        assert_eq!(
+            hex_to_rgb("blurple"),
+            Err("Unknown color: blurple".to_string())
+        );
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let contents = r##"
+            backend = "histogram"
+            bg_color = "#102030"
+            fg_color = "white"
+            cursor_color = "#abcdef"
+            crop = [1, 2, 3, 4]
+            light_map = [7, 6, 5, 4, 3, 2, 1, 0]
+            temperature = 3000.0
+
+            [presets.warm]
+            temperature = 4000.0
+        "##;
+        let config = Config::from_table(toml::from_str(contents).unwrap()).unwrap();
+        let serialized = config.to_toml().unwrap();
+        assert!(serialized.contains("bg_color = \"#102030\""));
+        assert!(serialized.contains("fg_color = \"#ffffff\""));
+
+        let reparsed = Config::from_table(toml::from_str(&serialized).unwrap()).unwrap();
+        assert_eq!(reparsed, config);
+    }
+
+    #[test]
+    fn colorthief_quality_range() {
+        for (quality, valid) in [(0, false), (1, true), (10, true), (11, false)] {
+            let config = Config {
+                colorthief_quality: quality,
+                ..Config::default()
+            };
+            assert_eq!(config.validate().is_ok(), valid, "{}", quality);
+        }
+    }
+
+    #[test]
+    fn every_field_is_documented() {
+        let config = Config::default();
+        let documented = FIELD_DOCS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let fields = config
+            .fields()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(documented, fields);
+    }
+
+    #[test]
+    fn sample_corners_needs_a_margin() {
+        let config = Config {
+            sample_corners: true,
+            corner_margin: 0.0,
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = Config {
+            corner_margin: 1.0,
+            ..config
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn commented_default_round_trips() {
+        let contents = Config::default().to_commented_toml().unwrap();
+        let table: toml::Table = toml::from_str(&contents).unwrap();
+        assert_eq!(Config::from_table(table).unwrap(), Config::default());
+    }
+}
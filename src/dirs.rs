@@ -13,6 +13,9 @@ pub static CONFIG_FILE: LazyLock<PathBuf> = LazyLock::new(|| {
     path
 });
 
+pub static SYSTEM_CONFIG_FILE: LazyLock<PathBuf> =
+    LazyLock::new(|| PathBuf::from("/etc/rwal/config.toml"));
+
 pub static CACHE_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
     let mut path = dirs::cache_dir().unwrap();
     path.push("rwal");
@@ -36,3 +39,21 @@ pub static CURRENT_COLORSCHEME_FILE: LazyLock<PathBuf> = LazyLock::new(|| {
     path.push("colors");
     path
 });
+
+pub static LAST_IMAGE_FILE: LazyLock<PathBuf> = LazyLock::new(|| {
+    let mut path = CACHE_DIR.clone();
+    path.push("last_image");
+    path
+});
+
+pub static CURSOR_FILE: LazyLock<PathBuf> = LazyLock::new(|| {
+    let mut path = CACHE_DIR.clone();
+    path.push("cursor");
+    path
+});
+
+pub static DOWNLOADS_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
+    let mut path = CACHE_DIR.clone();
+    path.push("downloads");
+    path
+});
@@ -0,0 +1,165 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use palette::Hsv;
+use palette::RgbHue;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::rwal::from_hsv;
+use crate::rwal::to_hsv;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Harmony {
+    Complementary,
+    Triadic,
+    Analogous,
+}
+
+impl Harmony {
+    fn offsets(self) -> &'static [f32] {
+        match self {
+            Harmony::Complementary => &[0.0, 180.0],
+            Harmony::Triadic => &[0.0, 120.0, 240.0],
+            Harmony::Analogous => &[-30.0, 0.0, 30.0],
+        }
+    }
+
+    pub fn rebuild(self, dominant: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
+        if dominant.is_empty() {
+            return Vec::new();
+        }
+
+        let bases = dominant
+            .iter()
+            .take(2)
+            .map(|&c| to_hsv(c))
+            .collect::<Vec<Hsv>>();
+        let hues = bases
+            .iter()
+            .flat_map(|base| {
+                self.offsets()
+                    .iter()
+                    .map(move |&offset| (*base, base.hue + RgbHue::from_degrees(offset)))
+            })
+            .collect::<Vec<(Hsv, RgbHue)>>();
+
+        (0..8)
+            .map(|i| {
+                let (color, hue) = hues[i % hues.len()];
+                let shade = if (i / hues.len()) % 2 == 0 { 1.0 } else { 0.75 };
+                from_hsv(Hsv::new(
+                    hue,
+                    color.saturation,
+                    (color.value * shade).clamp(0.0, 1.0),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Harmony {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "complementary" => Ok(Harmony::Complementary),
+            "triadic" => Ok(Harmony::Triadic),
+            "analogous" => Ok(Harmony::Analogous),
+            _ => Err(format!("Unknown harmony: {}", s)),
+        }
+    }
+}
+
+impl Display for Harmony {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Harmony::Complementary => "complementary",
+            Harmony::Triadic => "triadic",
+            Harmony::Analogous => "analogous",
+        }
+        .fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rwal::PALETTE_SIZE;
+
+    const HARMONIES: [Harmony; 3] = [Harmony::Complementary, Harmony::Triadic, Harmony::Analogous];
+
+    fn hue(c: (u8, u8, u8)) -> f32 {
+        to_hsv(c).hue.into_positive_degrees()
+    }
+
+    fn assert_hue(c: (u8, u8, u8), expected: f32) {
+        let diff = (hue(c) - expected).rem_euclid(360.0);
+        assert!(
+            diff.min(360.0 - diff) < 2.0,
+            "{:?} has hue {}, expected {}",
+            c,
+            hue(c),
+            expected
+        );
+    }
+
+    #[test]
+    fn one_dominant_color_cycles_its_offsets() {
+        let red = (255, 0, 0);
+        for harmony in HARMONIES {
+            let palette = harmony.rebuild(&[red]);
+            assert_eq!(palette.len(), PALETTE_SIZE, "{}", harmony);
+
+            let offsets = harmony.offsets();
+            for (i, &c) in palette.iter().enumerate() {
+                assert_hue(c, offsets[i % offsets.len()]);
+            }
+            let base = offsets.iter().position(|&o| o == 0.0).unwrap();
+            assert_eq!(palette[base], red);
+        }
+    }
+
+    #[test]
+    fn two_dominant_colors_interleave_their_offsets() {
+        let (red, blue) = ((255, 0, 0), (0, 0, 255));
+        for harmony in HARMONIES {
+            let palette = harmony.rebuild(&[red, blue, (0, 255, 0)]);
+            assert_eq!(palette.len(), PALETTE_SIZE, "{}", harmony);
+
+            let offsets = harmony.offsets();
+            let hues = [0.0, 240.0]
+                .iter()
+                .flat_map(|base| offsets.iter().map(move |offset| base + offset))
+                .collect::<Vec<f32>>();
+            for (i, &c) in palette.iter().enumerate() {
+                assert_hue(c, hues[i % hues.len()]);
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_hues_are_darker() {
+        let palette = Harmony::Complementary.rebuild(&[(255, 0, 0)]);
+        let value = |c| to_hsv(c).value;
+        assert!((value(palette[0]) - 1.0).abs() < 0.01);
+        assert!((value(palette[2]) - 0.75).abs() < 0.01);
+        assert!((value(palette[4]) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn no_dominant_colors_build_nothing() {
+        for harmony in HARMONIES {
+            assert!(harmony.rebuild(&[]).is_empty());
+        }
+    }
+
+    #[test]
+    fn harmony_names() {
+        for harmony in HARMONIES {
+            assert_eq!(harmony.to_string().parse::<Harmony>(), Ok(harmony));
+        }
+        assert!("tetradic".parse::<Harmony>().is_err());
+    }
+}
@@ -0,0 +1,67 @@
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+pub fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let mut cache_path = crate::dirs::DOWNLOADS_DIR.clone();
+    cache_path.push(
+        url.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>(),
+    );
+
+    if let Ok(bytes) = std::fs::read(&cache_path) {
+        log::info!("Using cached download {}", cache_path.display());
+        return Ok(bytes);
+    }
+
+    let bytes = download(url)?;
+
+    if let Err(e) = std::fs::create_dir_all(crate::dirs::DOWNLOADS_DIR.as_path())
+        .and_then(|_| std::fs::write(&cache_path, &bytes))
+    {
+        log::warn!("Failed to cache {}: {}", url, e);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(feature = "http")]
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    log::info!("Downloading {}", url);
+
+    let mut response = ureq::get(url)
+        .call()
+        .map_err(|e| format!("Failed to download {}: {}", url, e))?;
+
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if !content_type.starts_with("image/") {
+        return Err(format!("{} is not an image ({})", url, content_type));
+    }
+
+    response
+        .body_mut()
+        .with_config()
+        .limit(64 * 1024 * 1024)
+        .read_to_vec()
+        .map_err(|e| format!("Failed to download {}: {}", url, e))
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    Err(format!(
+        "Cannot download {}: rwal was built without the http feature",
+        url
+    ))
+}
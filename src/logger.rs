@@ -0,0 +1,52 @@
+use std::io::Write;
+use std::time::SystemTime;
+
+struct JsonLogger {
+    level: log::LevelFilter,
+}
+
+impl log::Log for JsonLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+
+        let line = serde_json::json!({
+            "timestamp": timestamp,
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        });
+
+        let _ = writeln!(std::io::stderr(), "{}", line);
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn init(format: &str, level: log::LevelFilter) -> Result<(), String> {
+    match format {
+        "pretty" => {
+            pretty_env_logger::formatted_builder()
+                .filter_level(level)
+                .init();
+        }
+        "json" => {
+            log::set_boxed_logger(Box::new(JsonLogger { level }))
+                .map_err(|e| format!("Failed to set logger: {}", e))?;
+            log::set_max_level(level);
+        }
+        _ => return Err(format!("Unknown log format: {}", format)),
+    }
+
+    Ok(())
+}
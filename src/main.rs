@@ -1,25 +1,137 @@
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
 
 use crate::config::{hex_to_rgb, rgb_to_hex};
 
 mod backends;
+mod completions;
 mod config;
 mod dirs;
+mod harmony;
+mod http;
+mod logger;
+mod orientation;
+mod pages;
 mod rwal;
+mod wallpaper;
 
 const HELP_MESSAGE: &str = r#"
 usage: rwal -i [path/to/image]
 
+boolean flags also accept --flag=true and --flag=false
+
 flags:
     -v                      verbose logging
-    -q                      quite logging
+    -q                      quite logging (failures still exit nonzero)
+    --log-format <format>   log format ("pretty" | "json", one object per line)
     -i <path>               image/path-with-images to generate coloscheme from
-    -l                      generate light colorscheme
-    -c                      skip cache
+                            (repeated or comma-separated paths are blended into one palette)
+                            (http(s) urls are downloaded when built with the http feature)
+    --repeat-last           generate from the image(s) of the last run instead of -i
+    --pick-seed <value>     seed for picking the image of a directory, so the same seed
+                            always picks the same file
+    --from-color <color>    generate the colorscheme from a single color instead of an image
+    -l                      generate light colorscheme, swaps bg and fg and darkens and
+                            desaturates color1-6/9-14 until they reach a 4.5:1 contrast
+                            on the light background
+    -c                      skip cache (neither read nor write it)
+    --force                 regenerate even if cached and overwrite the cache
+    -o --out <path>         also write the colors to this path
+    --format <format>       format of -o ("hex" | "labeled" | "xresources" | "windows-terminal"
+                            | "base16" | "base24")
+                            labeled appends '# colorN' to each line, color0/7 are the
+                            background/foreground and color8/15 their bright variants,
+                            followed by a '# cursor' line
+                            base16 writes a base16 scheme yaml, base00-07 step from the
+                            background to the foreground (base02 is color8, base07 is
+                            color15) and base08-0F are red, orange, yellow, green, cyan,
+                            blue, magenta and brown; base24 adds darker backgrounds as
+                            base10-11 and the bright colors as base12-17
+    --hex-case <case>       case of hex colors written by -o ("lower" | "upper")
+    --color-notation <notation>
+                            notation of colors written by -o ("hex" | "rgb"), rgb writes
+                            'rgb(r, g, b)' in the hex and labeled formats
+    --accent                print the accent color (most vivid of color1-6)
+    --explain               print how each color was derived from the palette, implies
+                            --force and writes nothing
+    --palette-weights       print the raw backend clusters and their weights as json
+    --count-distinct        print how many pixels and distinct colors of each image
+                            survive filtering and exit
+    --average               print the average color of each image (in linear light,
+                            after filtering) and exit
+    --check-contrast        print the contrast of color1-15 against color0 (4.5:1 to pass)
+    --strict-contrast       exit with an error if --check-contrast finds a failing color
+    --hook <cmd>            run this shell command after the colors are written, with
+                            RWAL_COLORS, RWAL_WALLPAPER and RWAL_PREVIEW set
+    --hook-on-cache         also run the hook when the colorscheme was cached
+    --no-preview            do not write preview.html (write_preview = false in config)
+    --update-terminals      send the colors to every open terminal
+    --json-summary          print a json object with the image, backend, whether the
+                            cache was hit and the colors to stdout at the end
+    --set-wallpaper <setter>
+                            also set the image as the wallpaper
+                            ("feh" | "swww" | "hyprpaper" | "swaybg")
+    --cache-info <image>    print the cached metadata for an image
+    --phash-threshold <value>
+                            reuse the cache of a near-identical image if their
+                            perceptual hashes differ in at most this many bits (0-64, 0 = off)
+    --compare <a> <b>       print both images' colorschemes side by side with their
+                            per-color delta E (also accepts <a>,<b>)
+    --restore               reapply the current colorscheme without an image
+    --precache <dir>        generate and cache a colorscheme for every image in dir (in
+                            parallel, with the current config) and skip cached ones
     --help -h               show this message
-    --backend <backend>     set backend ("colorz" | "colorthief")
+    --generate-completions <shell>
+                            print a completion script ("bash" | "zsh" | "fish")
+    --config <path>         read config from this .toml or .json file
+                            (merged over /etc/rwal/config.toml if it exists)
+    --stdin-config          read the whole config as toml from stdin instead of any
+                            config file, flags still override it
+    --init-config           write the default config to the config path and exit
+    --preset <name>         apply the [presets.<name>] table of the config over it,
+                            before the environment and other flags
+    --fast                  preset that trades quality for latency: colorthief backend,
+                            a 50x50 thumb, no clamps or skips and a single kmeans run
+    --quality               preset that trades latency for quality: colorz backend, a
+                            200x200 thumb, 10 kmeans runs and 3 retries
+                            (other flags still override parts of a preset)
+    --backend <backend>     set backend ("colorz" | "colorthief" | "histogram")
+    --passthrough           use the image colors as is when there are at most 16 of them
+                            (before filtering), keeping the first 8 in reading order,
+                            instead of running the backend
+    --kmeans-runs <value>   kmeans runs of the colorz backend, the best one is kept (min=1)
+    --colorthief-quality <value>
+                            colorthief sampling step (1-10, 1 = slowest and most accurate)
+    --timeout <secs>        fall back to colorthief if the backend takes longer
+    --max-retries <value>   rerun the backend with new seeds until it yields 8
+                            distinct colors
+    --seed <value>          seed for the backend and random sampling
+    --thumb <value>         set thumb width and height (min=1)
+    --thumb-scale <pct>     size the thumb relative to the image (0-100), overrides
+                            the fixed thumb size
+    --max-pixels <value>    size the thumb to at most this many pixels keeping its
+                            aspect ratio, overrides the other thumb sizes
     --thumb-w <value>       set thumb width (min=1)
     --thumb-h <value>       set thumb height (min=1)
+    --preserve-aspect       fit the thumb within its size instead of stretching it
+    --page <n>              sample this page of a multi-page tiff or frame of an animated
+                            gif/png/webp (default 0)
+    --respect-exif          rotate photos by their EXIF orientation before sampling
+    --center-weight <value> favor pixels near the image center (0.0 - 1.0, 0 = uniform)
+    --edge-weight <value>   favor detailed regions by weighting pixels with their sobel
+                            gradient magnitude (0.0 - 1.0, 0 = uniform)
+    --sample-corners        only sample pixels near the edges of the thumb, for theming
+                            from screenshots
+    --corner-margin <pct>   width of the --sample-corners band in percent of the thumb
+                            size (0-50, at least 1 with --sample-corners, default 10)
+    --sample-stride <n>     only sample every nth pixel of the thumb
+    --sample-random <count> only sample this many random pixels of the thumb
+    --crop <x,y,w,h>        crop the image to this rectangle before sampling
+    --crop-center <pct>     crop the image to its centered pct% (0-100)
     --clamp-s-min <value>   set min saturation clamp (0.0 - 1.0)
     --clamp-s-max <value>   set max saturation clamp (0.0 - 1.0)
     --clamp-v-min <value>   set min value clamp (0.0 - 1.0)
@@ -28,57 +140,1153 @@ flags:
     --skip-s-max <value>    set max saturation skip (0.0 - 1.0)
     --skip-v-min <value>    set min value skip (0.0 - 1.0)
     --skip-v-max <value>    set max value skip (0.0 - 1.0)
-    --skip-value            skip value
-    --skip-saturation       skip saturation
+    --skip-value            skip value (keeps only colors inside the value skip range)
+    --skip-saturation       skip saturation (keeps only colors inside the saturation
+                            skip range)
+    --skip-invert           make --skip-value/--skip-saturation remove the colors inside
+                            their range instead, set skip_invert = true in the config
+                            to switch to these semantics
+    --skip-inclusive        keep colors exactly at the skip bounds (by default the bounds
+                            are exclusive, so a 0.0 - 1.0 range drops pure black/white)
     --clamp-value           clamp value
     --clamp-saturation      clamp saturation
+    --clamp-mode <mode>     "hard" clamps into the range, "soft" rescales the whole
+                            0.0 - 1.0 range into it keeping relative differences
+    --filter-space <space>  space the skip/clamp bounds apply in ("hsv" | "hsl" | "lab")
+                            saturation maps to hsl saturation or lab chroma, value to
+                            hsl/lab lightness
+    --exclude-extremes      drop near-black and near-white pixels
+    --exclude-dark <value>  drop pixels with a value below this (0.0 - 1.0)
+    --exclude-light <value> drop pixels with a whiteness above this (0.0 - 1.0)
     --bg-idx <value>        palette color to mix with bg (0-7)
     --fg-idx <value>        palette color to mix with fg (0-7)
+    --auto-bg-fg            mix bg with the darkest and fg with the lightest palette color,
+                            overriding --bg-idx/--fg-idx (swapped with -l)
     --bg-str <value>        amount of palette color to apply to bg (0-100)
     --fg-str <value>        amount of palette color to apply to fg (0-100)
-    --bg <value>            background color (#HHEEXX)
-    --fg <value>            foreground color (#HHEEXX)
+    --bg <value>            background color (#HHEEXX or a color name)
+    --bg-from-corner <region>
+                            use the average color of a region of the image as the
+                            background color, overriding --bg
+                            ("topleft" | "topright" | "bottomleft" | "bottomright" | "center")
+                            the region is a tenth of the thumb in each direction and is
+                            still mixed by --bg-str, 0 uses the sampled color as is
+    --fg <value>            foreground color (#HHEEXX or a color name)
+    --cursor <value>        cursor color (#HHEEXX or a color name), overrides --cursor-idx
+    --cursor-idx <value>    color to use as the cursor (0-15, default 7 = fg)
+                            the cursor is kept out of the colors file, in cache/cursor
+    --bg-mode <mode>        how bg is derived ("fixed" | "darken" | "lighten")
+                            darken/lighten shift palette[bg-idx] by bg-str percent
+                            and ignore --bg
+    --fg-mode <mode>        how fg is derived ("fixed" | "darken" | "lighten")
+    --base-only             only write color0-7, the colors file then has 8 lines
+    --base-theme <path>     keep the 16 colors of this file and only take the generated
+                            colors at --merge-indices
+    --merge-indices <list>  comma-separated indices taken from the generated colors when
+                            using --base-theme (default 1,2,3,4,5,6)
+    --linear-mix            mix colors in linear light instead of raw sRGB
+    --temperature <kelvin>  shift the colorscheme warmer/cooler (1000-40000, 6500 = none)
+    --hue-rotate <degrees>  rotate every color around the hue wheel
+    --bias-hue <degrees>    pull every color's hue toward this hue
+    --bias-strength <value> how far --bias-hue pulls, from 0 (no-op) to 1 (all hues
+                            become the target, default 0.5)
+    --simulate <kind>       simulate color blindness in the preview
+                            ("protanopia" | "deuteranopia" | "tritanopia")
+    --apply-simulation      also apply the simulation to the written colors
+    --min-distance <value>  nudge apart palette colors closer than this CIELAB delta E
+    --threshold-colors <value>
+                            replace palette colors closer than this CIELAB delta E to an
+                            earlier one with the next distinct backend cluster, unlike
+                            --min-distance which only moves them apart; the bright
+                            colors follow since they are derived from the palette
+    --sort-by <order>       palette order ("hue" | "luminance" | "saturation")
+                            by default the whole palette is sorted, so --bg-idx/--fg-idx
+                            refer to positions after sorting
+    --pin-bg-fg             pick the bg/fg palette colors before sorting and only sort
+                            colors 1-6
+    --pywal-order           reorder the final colors the way pywal templates expect:
+                            color0 background, color1-6 from darkest to lightest,
+                            color7 foreground, color8 bright background, color9-14
+                            the bright variants of color1-6 in the same order and
+                            color15 bright foreground
+    --harmony <kind>        rebuild the palette from the 1-2 most dominant colors using
+                            hue wheel relationships
+                            ("complementary" | "triadic" | "analogous")
+    --order-by-dominance    order the palette by how many pixels each color covers,
+                            most common first, instead of --sort-by (this changes
+                            which color lands at each index)
+    --normalize-lightness   set every palette color to the same oklch lightness, keeping
+                            hue and chroma, before bg/fg are mixed
+    --target-lightness <value>
+                            oklch lightness for --normalize-lightness (0.0 - 1.0, 0.7)
+
+environment:
+    RWAL_IMAGE, RWAL_BACKEND, RWAL_LIGHT (true | false), RWAL_SORT_BY, RWAL_FORMAT,
+    RWAL_BG and RWAL_FG take the place of the matching flags
+    flags override the environment, which overrides the config file
 "#;
 
-fn main() {
+fn main() -> ExitCode {
     let mut flag = flag::Flag::new();
 
     if flag.get_bool("-h") || flag.get_bool("--help") {
         println!("{HELP_MESSAGE}");
-        return;
+        return ExitCode::SUCCESS;
     }
 
-    unsafe {
-        if flag.get_bool("-v") {
-            std::env::set_var("RUST_LOG", "trace");
-        } else if flag.get_bool("-q") {
-            std::env::set_var("RUST_LOG", "none");
-        } else {
-            std::env::set_var("RUST_LOG", "info");
+    if let Some(shell) = flag.get_str("--generate-completions") {
+        return match completions::generate(&shell) {
+            Ok(script) => {
+                print!("{script}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let level = if flag.get_bool("-v") {
+        log::LevelFilter::Trace
+    } else if flag.get_bool("-q") {
+        log::LevelFilter::Off
+    } else {
+        log::LevelFilter::Info
+    };
+
+    let log_format = flag
+        .get_str("--log-format")
+        .unwrap_or_else(|| "pretty".to_string());
+    if let Err(e) = logger::init(&log_format, level) {
+        eprintln!("{e}");
+        return ExitCode::FAILURE;
+    }
+
+    let known = completions::FLAGS
+        .iter()
+        .map(|(name, _, _)| *name)
+        .collect::<Vec<&str>>();
+    let unknown = flag.validate_known(&known);
+
+    if !unknown.is_empty() {
+        log::error!("Unknown flags: {}", unknown.join(", "));
+        log::info!("See rwal --help");
+        return ExitCode::FAILURE;
+    }
+
+    let config_file = flag
+        .get_str("--config")
+        .map(std::path::PathBuf::from)
+        .unwrap_or(crate::dirs::CONFIG_FILE.clone());
+
+    if flag.get_bool("--init-config") {
+        if let Err(e) = config::Config::write_default(&config_file) {
+            log::error!("Failed to write config: {}", e);
+            return ExitCode::FAILURE;
+        }
+
+        log::info!("Wrote default config to {}", config_file.display());
+        return ExitCode::SUCCESS;
+    }
+
+    let mut config = if flag.get_bool("--stdin-config") {
+        match config::Config::from_stdin() {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("Failed to read config from stdin: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        match config::Config::from_file(&config_file) {
+            Ok(config) => {
+                log::info!("Config collected");
+                config
+            }
+            Err(e)
+                if e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+            {
+                log::info!(
+                    "No config at {}, using default (run rwal --init-config to create one)",
+                    config_file.display()
+                );
+                Default::default()
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                log::warn!("Failed to read config, using default");
+                Default::default()
+            }
+        }
+    };
+
+    if let Some(preset) = flag.get_str("--preset")
+        && let Err(e) = config.apply_named_preset(&preset)
+    {
+        log::error!("{}", e);
+        log::info!("Exiting...");
+        return ExitCode::FAILURE;
+    }
+
+    log::info!("Reading environment");
+
+    if let Err(e) = read_env(&mut config) {
+        log::error!("{}", e);
+        log::info!("Exiting...");
+        return ExitCode::FAILURE;
+    }
+
+    log::info!("Reading flags");
+
+    if let Err(e) = read_flags(&mut flag, &mut config) {
+        log::error!("{}", e);
+        log::info!("Exiting...");
+        return ExitCode::FAILURE;
+    }
+
+    if let Err(e) = config.validate() {
+        log::error!("{}", e);
+        log::info!("Exiting...");
+        return ExitCode::FAILURE;
+    }
+
+    if config.light {
+        std::mem::swap(&mut config.bg_color, &mut config.fg_color);
+    }
+
+    let update = flag.get_bool("--update-terminals");
+
+    let pick_seed = match flag
+        .get_str("--pick-seed")
+        .map(|v| {
+            v.parse::<u64>()
+                .map_err(|e| format!("Invalid pick seed {}: {}", v, e))
+        })
+        .transpose()
+    {
+        Ok(pick_seed) => pick_seed,
+        Err(e) => {
+            log::error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if flag.get_bool("--restore") {
+        log::info!("Restoring colorscheme");
+
+        let colorscheme = match read_colorscheme(crate::dirs::CURRENT_COLORSCHEME_FILE.as_path()) {
+            Ok(mut colorscheme) => {
+                if let Ok(cursor) = std::fs::read_to_string(&*crate::dirs::CURSOR_FILE)
+                    .map_err(|e| e.to_string())
+                    .and_then(|cursor| hex_to_rgb(cursor.trim()))
+                {
+                    colorscheme.cursor = cursor;
+                }
+                colorscheme
+            }
+            Err(e) => {
+                log::error!("Failed to restore colorscheme: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if config.write_preview {
+            let _ = std::fs::write(
+                crate::dirs::HTML_PREVIEW_FILE.clone(),
+                colorscheme.html_preview(),
+            );
+        }
+
+        if update {
+            update_terminals(&colorscheme.to_sequences());
+        }
+
+        log::info!("Exiting...");
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(image) = flag.get_str("--cache-info") {
+        let info_path = cache_info_path(&cache_path(&config, std::slice::from_ref(&image)));
+
+        match std::fs::read_to_string(&info_path) {
+            Ok(info) => println!("{}", info),
+            Err(e) => {
+                log::error!("No cache info for {}: {}", image, e);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        log::info!("Exiting...");
+        return ExitCode::SUCCESS;
+    }
+
+    let mut compare = flag.get_values("--compare", 2);
+    if compare.len() != 2 {
+        compare = flag.get_vec("--compare");
+    }
+
+    if !compare.is_empty() {
+        let [f, s] = compare.as_slice() else {
+            log::error!("--compare takes exactly two images");
+            return ExitCode::FAILURE;
+        };
+
+        let rwal = rwal::Rwal::from(&config);
+        let mut colorschemes = Vec::new();
+
+        for image in [f, s] {
+            match resolve_image(image, pick_seed)
+                .and_then(|image| rwal.generate_colorscheme(&[image]))
+            {
+                Ok(colorscheme) => colorschemes.push(colorscheme),
+                Err(e) => {
+                    log::error!("Failed to get colorscheme for {}: {}", image, e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+
+        print_comparison(&colorschemes[0], &colorschemes[1]);
+
+        log::info!("Exiting...");
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(color) = flag.get_str("--from-color") {
+        let color = match hex_to_rgb(&color) {
+            Ok(color) => color,
+            Err(e) => {
+                log::error!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        log::info!("Generating colorscheme from {}", rgb_to_hex(color));
+        let colorscheme = rwal::Rwal::from(&config).generate_from_color(color);
+
+        if config.write_preview {
+            let _ = std::fs::write(
+                crate::dirs::HTML_PREVIEW_FILE.clone(),
+                colorscheme.html_preview(),
+            );
+        }
+        write_current(&colorscheme);
+
+        if let Some(out) = flag.get_str("-o").or(flag.get_str("--out"))
+            && let Err(e) = write_output(
+                &out,
+                colorscheme
+                    .format(config.format, config.color_style())
+                    .as_bytes(),
+            )
+        {
+            log::error!("Failed to write {}: {}", out, e);
+            return ExitCode::FAILURE;
+        }
+
+        if update {
+            update_terminals(&colorscheme.to_sequences());
+        }
+
+        if flag.get_bool("--accent") {
+            println!("{}", rgb_to_hex(colorscheme.accent()));
+        }
+
+        log::info!("Exiting...");
+        return ExitCode::SUCCESS;
+    }
+
+    let base_theme = match config.base_theme.as_deref().map(|path| {
+        read_colorscheme(std::path::Path::new(path))
+            .map_err(|e| format!("Failed to read base theme: {}", e))
+    }) {
+        Some(Ok(base)) => Some(base),
+        Some(Err(e)) => {
+            log::error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+
+    if let Some(dir) = flag.get_str("--precache") {
+        let _ = std::fs::create_dir_all(crate::dirs::PREV_COLORSCHEMES_DIR.clone());
+
+        let images = collect_images(std::path::Path::new(&dir))
+            .into_iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+
+        if images.is_empty() {
+            log::error!("No image files found at {}", dir);
+            return ExitCode::FAILURE;
         }
+
+        let (generated, cached, failed) = precache(&config, base_theme, &images);
+        println!(
+            "Precached {} of {} images ({} already cached, {} failed)",
+            generated,
+            images.len(),
+            cached,
+            failed
+        );
+
+        return if failed > 0 {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        };
     }
 
-    pretty_env_logger::init();
+    let (image, images) = if flag.get_bool("--repeat-last") {
+        match std::fs::read_to_string(&*crate::dirs::LAST_IMAGE_FILE) {
+            Ok(last) => (
+                last.trim().to_string(),
+                last.lines().map(String::from).collect::<Vec<String>>(),
+            ),
+            Err(_) => {
+                log::error!("No previous run recorded, use -i first");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        let env_image = std::env::var("RWAL_IMAGE").ok().filter(|v| !v.is_empty());
+        let Some(image) = flag.get_str("-i").or(env_image) else {
+            log::info!("No image path specified");
+            log::info!("Exiting...");
+            return ExitCode::SUCCESS;
+        };
 
-    let mut config = match config::Config::from_file(crate::dirs::CONFIG_FILE.clone()) {
-        Ok(config) => {
-            log::info!("Config collected");
-            config
+        let mut images = flag.get_vec("-i");
+        if images.is_empty() {
+            images = image.split(',').map(String::from).collect();
         }
+
+        (image, images)
+    };
+
+    let images = images
+        .iter()
+        .filter_map(|p| {
+            resolve_image(p, pick_seed)
+                .inspect_err(|e| log::warn!("{}, skipping", e))
+                .ok()
+        })
+        .collect::<Vec<String>>();
+
+    if images.is_empty() {
+        log::info!("No usable images at {}", &image);
+        log::info!("Exiting...");
+        return ExitCode::FAILURE;
+    }
+
+    let setter = match flag
+        .get_str("--set-wallpaper")
+        .map(|v| v.parse::<wallpaper::Setter>())
+        .transpose()
+    {
+        Ok(setter) => setter,
         Err(e) => {
             log::error!("{}", e);
-            log::warn!("Failed to read config, using default");
-            Default::default()
+            return ExitCode::FAILURE;
         }
     };
 
-    log::info!("Reading flags");
+    let skip_cache = flag.get_bool("-c");
+    let force = flag.get_bool("--force");
+    let out = flag.get_str("-o").or(flag.get_str("--out"));
+    let accent = flag.get_bool("--accent");
+    let palette_weights = flag.get_bool("--palette-weights");
+    let check_contrast = flag.get_bool("--check-contrast");
+    let strict_contrast = flag.get_bool("--strict-contrast");
+    let json_summary = flag.get_bool("--json-summary");
+    let explain = flag.get_bool("--explain");
+
+    let rwal = rwal::Rwal::from(&config);
+
+    if flag.get_bool("--count-distinct") {
+        for image in &images {
+            match rwal.count_distinct(image) {
+                Ok((pixels, kept, distinct)) => println!(
+                    "{}: {} distinct colors, {} of {} pixels kept after filtering",
+                    image, distinct, kept, pixels
+                ),
+                Err(e) => {
+                    log::error!("{}: {}", image, e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if flag.get_bool("--average") {
+        for image in &images {
+            match rwal.average_color(image) {
+                Ok(color) => println!("{}", rgb_to_hex(color)),
+                Err(e) => {
+                    log::error!("{}: {}", image, e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if palette_weights {
+        let weights = match rwal.palette_weights(&images) {
+            Ok(weights) => weights,
+            Err(e) => {
+                log::error!("{}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        let json = weights
+            .iter()
+            .map(|&(color, weight)| {
+                serde_json::json!({
+                    "color": rgb_to_hex(color),
+                    "weight": weight,
+                })
+            })
+            .collect::<Vec<serde_json::Value>>();
+
+        println!("{}", serde_json::Value::Array(json));
+        return ExitCode::SUCCESS;
+    }
+
+    if !crate::dirs::CACHE_DIR.exists() {
+        let _ = std::fs::create_dir_all(crate::dirs::CACHE_DIR.clone());
+    }
+
+    if !crate::dirs::PREV_COLORSCHEMES_DIR.exists() {
+        let _ = std::fs::create_dir_all(crate::dirs::PREV_COLORSCHEMES_DIR.clone());
+    }
+
+    if !explain && let Err(e) = std::fs::write(&*crate::dirs::LAST_IMAGE_FILE, images.join("\n")) {
+        log::warn!("Failed to record the last image: {}", e);
+    }
+
+    let cache_path = if skip_cache {
+        log::info!("Skipping cache");
+        None
+    } else if images.iter().any(|image| http::is_url(image)) {
+        log::info!("Skipping cache for downloaded images");
+        None
+    } else {
+        Some(cache_path(&config, &images))
+    };
+
+    let exact = exact_cache(cache_path.as_ref(), force || explain);
+
+    let hashes = match &cache_path {
+        Some(_) if exact.is_none() && !explain && config.phash_threshold > 0 => images
+            .iter()
+            .map(|image| rwal::image_hash(image))
+            .collect::<Result<Vec<u64>, &str>>()
+            .inspect_err(|e| log::warn!("Failed to hash images: {}", e))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+
+    let cached = match exact {
+        Some(cache_path) => Some(cache_path.clone()),
+        None if !force && !hashes.is_empty() => find_similar_cache(&config, &hashes),
+        None => None,
+    };
+
+    if let Some(cache_path) = &cached {
+        log::info!("Cache hit, using {}", cache_path.display());
+        let colorscheme = match read_cached_colorscheme(cache_path) {
+            Ok(colorscheme) => colorscheme,
+            Err(e) => {
+                log::error!("Failed to read cached colorscheme: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        write_current(&colorscheme);
+        if config.write_preview {
+            let preview = match config.simulate {
+                Some(simulate) if !config.apply_simulation => colorscheme.simulate(simulate),
+                _ => colorscheme,
+            };
+            let _ = std::fs::write(
+                crate::dirs::HTML_PREVIEW_FILE.clone(),
+                preview.html_preview(),
+            );
+        }
+        if let Some(out) = &out
+            && let Err(e) = write_output(
+                out,
+                colorscheme
+                    .format(config.format, config.color_style())
+                    .as_bytes(),
+            )
+        {
+            log::error!("Failed to write {}: {}", out, e);
+            return ExitCode::FAILURE;
+        }
+        if update {
+            update_terminals(&colorscheme.to_sequences());
+        }
+        if accent {
+            println!("{}", rgb_to_hex(colorscheme.accent()));
+        }
+        let contrast_ok = !check_contrast || report_contrast(&colorscheme);
+        if json_summary {
+            print_summary(&config, &images, true, &colorscheme);
+        }
+        if !contrast_ok && strict_contrast {
+            return ExitCode::FAILURE;
+        }
+        if let Some(setter) = setter {
+            setter.set(&images[0]);
+        }
+        if flag.get_bool("--hook-on-cache")
+            && let Some(hook) = &config.post_hook
+        {
+            run_hook(hook, &images);
+        }
+        log::info!("Exiting...");
+        return ExitCode::SUCCESS;
+    }
+
+    let decoded = match rwal.load_images(&images) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            log::error!("Failed to get colorscheme: {:#?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let palette = match rwal.generate_raw_palette(&decoded) {
+        Ok(palette) => palette,
+        Err(e) => {
+            log::error!("Failed to get colorscheme: {:#?}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let rwal = rwal.with_image_bg(&decoded);
+    drop(decoded);
+
+    let (mut colorscheme, mut notes) = rwal.build_colorscheme_explained(&palette);
+
+    if let Some(base) = base_theme {
+        log::info!("Merging into base theme");
+        colorscheme = colorscheme.merge_from(base, &config.merge_indices);
+        notes.push(format!(
+            "colors other than {:?} and the cursor come from the base theme",
+            config.merge_indices
+        ));
+    }
+
+    if explain {
+        println!("{}", notes.join("\n"));
+        log::info!("Exiting...");
+        return ExitCode::SUCCESS;
+    }
+
+    log::debug!("Generated colorscheme:\n{}", colorscheme);
+
+    let preview = match config.simulate {
+        Some(simulate) => {
+            log::info!("Simulating {}", simulate);
+            let simulated = colorscheme.simulate(simulate);
+            if config.apply_simulation {
+                colorscheme = simulated;
+            }
+            simulated
+        }
+        None => colorscheme,
+    };
+
+    if config.write_preview {
+        let _ = std::fs::write(
+            crate::dirs::HTML_PREVIEW_FILE.clone(),
+            preview.html_preview(),
+        );
+    }
+
+    if let Some(cache_path) = &cache_path {
+        write_cache(
+            cache_path,
+            &config,
+            &images,
+            &palette,
+            &hashes,
+            &colorscheme,
+        );
+    }
+
+    write_current(&colorscheme);
+
+    if let Some(out) = &out
+        && let Err(e) = write_output(
+            out,
+            colorscheme
+                .format(config.format, config.color_style())
+                .as_bytes(),
+        )
+    {
+        log::error!("Failed to write {}: {}", out, e);
+        return ExitCode::FAILURE;
+    }
+
+    if update {
+        update_terminals(&colorscheme.to_sequences());
+    }
+
+    if let Some(setter) = setter {
+        setter.set(&images[0]);
+    }
+
+    if let Some(hook) = &config.post_hook {
+        run_hook(hook, &images);
+    }
+
+    if accent {
+        println!("{}", rgb_to_hex(colorscheme.accent()));
+    }
+
+    let contrast_ok = !check_contrast || report_contrast(&colorscheme);
+
+    if json_summary {
+        print_summary(&config, &images, false, &colorscheme);
+    }
+
+    if !contrast_ok && strict_contrast {
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn print_summary(
+    config: &config::Config,
+    images: &[String],
+    cache_hit: bool,
+    colorscheme: &rwal::Colorscheme,
+) {
+    let colors = colorscheme
+        .into_array()
+        .iter()
+        .take(colorscheme.color_count())
+        .map(|c| rgb_to_hex(*c))
+        .collect::<Vec<String>>();
+
+    let summary = serde_json::json!({
+        "cache_hit": cache_hit,
+        "image": images.join(","),
+        "backend": config.backend.to_string(),
+        "colors": colors,
+    });
+
+    println!("{}", summary);
+}
+
+fn run_hook(hook: &str, images: &[String]) {
+    log::info!("Running hook {}", hook);
+
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .env(
+            "RWAL_COLORS",
+            crate::dirs::CURRENT_COLORSCHEME_FILE.as_os_str(),
+        )
+        .env("RWAL_WALLPAPER", images.join(","))
+        .env("RWAL_PREVIEW", crate::dirs::HTML_PREVIEW_FILE.as_os_str())
+        .status();
+
+    match status {
+        Ok(status) if status.success() => log::info!("Hook exited with {}", status),
+        Ok(status) => log::warn!("Hook exited with {}", status),
+        Err(e) => log::error!("Failed to run hook: {}", e),
+    }
+}
+
+fn print_comparison(f: &rwal::Colorscheme, s: &rwal::Colorscheme) {
+    let swatch = |c: (u8, u8, u8)| format!("\x1b[48;2;{};{};{}m    \x1b[0m", c.0, c.1, c.2);
+    let diff = f.diff(s);
+
+    for (i, (fc, sc)) in f.into_array().into_iter().zip(s.into_array()).enumerate() {
+        println!(
+            "color{:<2} {} {}  {} {}  \u{394}E {:.2}",
+            i,
+            swatch(fc),
+            rgb_to_hex(fc),
+            swatch(sc),
+            rgb_to_hex(sc),
+            diff[i]
+        );
+    }
+}
+
+fn report_contrast(colorscheme: &rwal::Colorscheme) -> bool {
+    let colors = colorscheme.into_array();
+    let mut passed = true;
+
+    for (i, color) in colors
+        .iter()
+        .enumerate()
+        .take(colorscheme.color_count())
+        .skip(1)
+    {
+        let ratio = rwal::Colorscheme::contrast_ratio(*color, colors[0]);
+        let pass = ratio >= 4.5;
+        passed &= pass;
+
+        println!(
+            "color{}: {} {:.2}:1 {}",
+            i,
+            rgb_to_hex(*color),
+            ratio,
+            if pass { "PASS" } else { "FAIL" }
+        );
+    }
+
+    passed
+}
+
+fn update_terminals(sequences: &str) {
+    let mut ttys = vec![std::path::PathBuf::from("/dev/tty")];
+
+    match std::fs::read_dir("/dev/pts") {
+        Ok(entries) => ttys.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.file_name().is_some_and(|name| name != "ptmx")),
+        ),
+        Err(e) => log::warn!("Failed to list /dev/pts: {}", e),
+    }
+
+    for tty in ttys {
+        let result = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&tty)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, sequences.as_bytes()));
+
+        match result {
+            Ok(()) => log::debug!("Updated {}", tty.display()),
+            Err(e) => log::warn!("Failed to update {}: {}", tty.display(), e),
+        }
+    }
+}
+
+fn cache_path(config: &config::Config, images: &[String]) -> std::path::PathBuf {
+    let name = images
+        .iter()
+        .map(|image| image.split("/").last().unwrap_or(image))
+        .collect::<Vec<&str>>()
+        .join(",");
+    let cache_name = format!("{}{}", config.cache_string(), name);
+    let cache_name = if cache_name.len() > 255 {
+        format!("{:016x}", config::fnv1a(cache_name.as_bytes()))
+    } else {
+        cache_name
+    };
+    let mut cache_path = crate::dirs::PREV_COLORSCHEMES_DIR.clone();
+    cache_path.push(cache_name);
+    cache_path
+}
+
+fn exact_cache(
+    cache_path: Option<&std::path::PathBuf>,
+    force: bool,
+) -> Option<&std::path::PathBuf> {
+    cache_path.filter(|cache_path| !force && cache_path.exists())
+}
+
+fn write_cache(
+    cache_path: &std::path::Path,
+    config: &config::Config,
+    images: &[String],
+    palette: &[(u8, u8, u8)],
+    hashes: &[u64],
+    colorscheme: &rwal::Colorscheme,
+) {
+    let _ = std::fs::write(cache_path, colorscheme.to_hex_lines());
+
+    let info = serde_json::json!({
+        "backend": config.backend.to_string(),
+        "cursor": rgb_to_hex(colorscheme.cursor),
+        "cache_key": config.cache_string(),
+        "images": images,
+        "palette": palette.iter().map(|c| rgb_to_hex(*c)).collect::<Vec<String>>(),
+        "phashes": hashes.iter().map(|h| format!("{:016x}", h)).collect::<Vec<String>>(),
+    });
+    let _ = std::fs::write(
+        cache_info_path(cache_path),
+        serde_json::to_string_pretty(&info).unwrap_or_default(),
+    );
+}
+
+fn precache(
+    config: &config::Config,
+    base_theme: Option<rwal::Colorscheme>,
+    images: &[String],
+) -> (usize, usize, usize) {
+    let rwal = rwal::Rwal::from(config);
+    let next = AtomicUsize::new(0);
+    let (generated, cached, failed) = (
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+    );
+
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(images.len());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(image) = images.get(i) else { break };
+                    let batch = std::slice::from_ref(image);
+
+                    let cache_path = cache_path(config, batch);
+                    if cache_path.exists() {
+                        log::info!("[{}/{}] {} is cached", i + 1, images.len(), image);
+                        cached.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+
+                    let decoded = match rwal.load_images(batch) {
+                        Ok(decoded) => decoded,
+                        Err(e) => {
+                            log::warn!("[{}/{}] {}: {}", i + 1, images.len(), image, e);
+                            failed.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                    };
+
+                    let palette = match rwal.generate_raw_palette(&decoded) {
+                        Ok(palette) => palette,
+                        Err(e) => {
+                            log::warn!("[{}/{}] {}: {}", i + 1, images.len(), image, e);
+                            failed.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                    };
+
+                    let mut colorscheme = rwal.with_image_bg(&decoded).build_colorscheme(&palette);
+                    if let Some(base) = base_theme {
+                        colorscheme = colorscheme.merge_from(base, &config.merge_indices);
+                    }
+                    if let Some(simulate) = config.simulate
+                        && config.apply_simulation
+                    {
+                        colorscheme = colorscheme.simulate(simulate);
+                    }
+
+                    let hashes = match config.phash_threshold {
+                        0 => Vec::new(),
+                        _ => rwal::image_hash(image).map(|h| vec![h]).unwrap_or_default(),
+                    };
+                    write_cache(&cache_path, config, batch, &palette, &hashes, &colorscheme);
+
+                    log::info!("[{}/{}] {} generated", i + 1, images.len(), image);
+                    generated.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    (
+        generated.into_inner(),
+        cached.into_inner(),
+        failed.into_inner(),
+    )
+}
+
+fn cache_info_path(cache_path: &std::path::Path) -> std::path::PathBuf {
+    let mut info_path = cache_path.as_os_str().to_owned();
+    info_path.push(".json");
+    std::path::PathBuf::from(info_path)
+}
+
+fn find_similar_cache(config: &config::Config, hashes: &[u64]) -> Option<std::path::PathBuf> {
+    let cache_key = config.cache_string();
+
+    let (distance, info_path) = std::fs::read_dir(crate::dirs::PREV_COLORSCHEMES_DIR.as_path())
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let info: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+
+            if info["cache_key"].as_str() != Some(cache_key.as_str()) {
+                return None;
+            }
+
+            let cached = info["phashes"]
+                .as_array()?
+                .iter()
+                .map(|h| u64::from_str_radix(h.as_str()?, 16).ok())
+                .collect::<Option<Vec<u64>>>()?;
+
+            if cached.len() != hashes.len() {
+                return None;
+            }
+
+            let distances = cached
+                .iter()
+                .zip(hashes)
+                .map(|(a, b)| (a ^ b).count_ones())
+                .collect::<Vec<u32>>();
+
+            if distances.iter().any(|&d| d > config.phash_threshold) {
+                return None;
+            }
+
+            Some((distances.iter().sum::<u32>(), path))
+        })
+        .min_by_key(|(distance, _)| *distance)?;
+
+    let cache_path = info_path.with_extension("");
+    if !cache_path.exists() {
+        return None;
+    }
+
+    log::info!(
+        "Reusing similar cache {} (distance {})",
+        cache_path.display(),
+        distance
+    );
+    Some(cache_path)
+}
+
+fn write_output(path: &str, contents: &[u8]) -> std::io::Result<()> {
+    let path = std::path::Path::new(path);
+
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, contents)
+}
+
+fn read_env(config: &mut config::Config) -> Result<(), String> {
+    apply_env(config, |key| std::env::var(key).ok())
+}
+
+fn apply_env(
+    config: &mut config::Config,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    let var = |key: &str| env(key).filter(|v| !v.is_empty());
+
+    config.backend = var("RWAL_BACKEND")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.backend);
+
+    config.light = var("RWAL_LIGHT")
+        .map(|v| v.parse().map_err(|_| format!("Invalid RWAL_LIGHT: {}", v)))
+        .transpose()?
+        .unwrap_or(config.light);
+
+    config.sort_by = var("RWAL_SORT_BY")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.sort_by);
+
+    config.format = var("RWAL_FORMAT")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.format);
+
+    config.bg_color = var("RWAL_BG")
+        .map(|v| hex_to_rgb(&v))
+        .transpose()?
+        .unwrap_or(config.bg_color);
+
+    config.fg_color = var("RWAL_FG")
+        .map(|v| hex_to_rgb(&v))
+        .transpose()?
+        .unwrap_or(config.fg_color);
+
+    Ok(())
+}
+
+fn read_flags(flag: &mut flag::Flag, config: &mut config::Config) -> Result<(), String> {
+    if flag.get_bool("--fast") {
+        config.apply_preset(config::Preset::Fast);
+    }
+
+    if flag.get_bool("--quality") {
+        config.apply_preset(config::Preset::Quality);
+    }
 
     config.backend = flag
         .get_str("--backend")
-        .map(backends::Backend::from)
+        .map(|v| v.parse())
+        .transpose()?
         .unwrap_or(config.backend);
 
+    config.kmeans_runs = flag
+        .get_u32("--kmeans-runs")
+        .map(|v| v.max(1))
+        .unwrap_or(config.kmeans_runs);
+
+    if let Some(quality) = flag.get_u32("--colorthief-quality") {
+        if !(1..=10).contains(&quality) {
+            return Err("--colorthief-quality must be between 1 and 10".into());
+        }
+        config.colorthief_quality = quality as u8;
+    }
+
+    config.timeout = flag
+        .get_u32("--timeout")
+        .map(|v| v as u64)
+        .or(config.timeout);
+
+    config.max_retries = flag.get_u32("--max-retries").unwrap_or(config.max_retries);
+
+    if let Some(threshold) = flag.get_u32("--phash-threshold") {
+        if threshold > 64 {
+            return Err("--phash-threshold must be between 0 and 64".into());
+        }
+        config.phash_threshold = threshold;
+    }
+
+    config.seed = flag
+        .get_str("--seed")
+        .map(|v| v.parse().map_err(|e| format!("Invalid seed {}: {}", v, e)))
+        .transpose()?
+        .unwrap_or(config.seed);
+
+    config.sample_stride = flag
+        .get_u32("--sample-stride")
+        .unwrap_or(config.sample_stride);
+
+    config.sample_random = flag.get_u32("--sample-random").or(config.sample_random);
+
+    if let Some(thumb) = flag.get_u32("--thumb").map(|v| v.clamp(1, 99999)) {
+        config.thumb_w = thumb;
+        config.thumb_h = thumb;
+    }
+
+    config.thumb_scale = flag
+        .get_f32("--thumb-scale")
+        .map(|v| v.clamp(0.01, 100.0))
+        .or(config.thumb_scale);
+
+    config.max_pixels = flag
+        .get_u32("--max-pixels")
+        .map(|v| v.max(1))
+        .or(config.max_pixels);
+
     config.thumb_w = flag
         .get_u32("--thumb-w")
         .map(|v| v.clamp(1, 99999))
@@ -89,6 +1297,36 @@ fn main() {
         .map(|v| v.clamp(1, 99999))
         .unwrap_or(config.thumb_h);
 
+    config.crop = flag
+        .get_str("--crop")
+        .map(|v| parse_crop(&v))
+        .transpose()?
+        .or(config.crop);
+
+    config.crop_center = flag
+        .get_f32("--crop-center")
+        .map(|v| v.clamp(0.0, 100.0))
+        .or(config.crop_center);
+
+    config.center_weight = flag
+        .get_f32("--center-weight")
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(config.center_weight);
+
+    config.edge_weight = flag
+        .get_f32("--edge-weight")
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(config.edge_weight);
+
+    config.sample_corners = flag
+        .get_opt_bool("--sample-corners")?
+        .unwrap_or(config.sample_corners);
+
+    config.corner_margin = flag
+        .get_f32("--corner-margin")
+        .map(|v| v.clamp(0.0, 50.0))
+        .unwrap_or(config.corner_margin);
+
     config.clamp_saturation_min = flag
         .get_f32("--clamp-s-min")
         .map(|v| v.clamp(0.0, 1.0))
@@ -129,6 +1367,16 @@ fn main() {
         .map(|v| v.clamp(0.0, 1.0))
         .unwrap_or(config.skip_value_max);
 
+    config.exclude_dark = flag
+        .get_f32("--exclude-dark")
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(config.exclude_dark);
+
+    config.exclude_light = flag
+        .get_f32("--exclude-light")
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(config.exclude_light);
+
     config.bg_color = flag
         .get_str("--bg")
         .and_then(|v| hex_to_rgb(&v).ok())
@@ -139,6 +1387,17 @@ fn main() {
         .and_then(|v| hex_to_rgb(&v).ok())
         .unwrap_or(config.fg_color);
 
+    config.cursor_color = flag
+        .get_str("--cursor")
+        .map(|v| hex_to_rgb(&v))
+        .transpose()?
+        .or(config.cursor_color);
+
+    config.cursor_idx = flag
+        .get_u32("--cursor-idx")
+        .map(|v| v as usize)
+        .unwrap_or(config.cursor_idx);
+
     config.bg_idx = flag
         .get_u32("--bg-idx")
         .map(|v| v as usize)
@@ -149,6 +1408,10 @@ fn main() {
         .map(|v| v as usize)
         .unwrap_or(config.fg_idx);
 
+    config.auto_bg_fg = flag
+        .get_opt_bool("--auto-bg-fg")?
+        .unwrap_or(config.auto_bg_fg);
+
     config.bg_strength = flag
         .get_u32("--bg-str")
         .map(|v| v as u8)
@@ -159,162 +1422,290 @@ fn main() {
         .map(|v| v as u8)
         .unwrap_or(config.fg_strength);
 
-    config.skip_value |= flag.get_bool("--skip-value");
-    config.skip_saturation |= flag.get_bool("--skip-saturation");
-    config.clamp_value |= flag.get_bool("--clamp-value");
-    config.clamp_saturation |= flag.get_bool("--clamp-saturation");
-    config.light |= flag.get_bool("-l");
+    config.min_distance = flag
+        .get_f32("--min-distance")
+        .map(|v| v.max(0.0))
+        .or(config.min_distance);
+
+    config.threshold_colors = flag
+        .get_f32("--threshold-colors")
+        .map(|v| v.max(0.0))
+        .or(config.threshold_colors);
+
+    config.simulate = flag
+        .get_str("--simulate")
+        .map(|v| v.parse())
+        .transpose()?
+        .or(config.simulate);
+
+    config.temperature = flag
+        .get_f32("--temperature")
+        .map(|v| v.clamp(1000.0, 40000.0))
+        .or(config.temperature);
+
+    config.hue_rotate = flag
+        .get_f32("--hue-rotate")
+        .map(|v| v.rem_euclid(360.0))
+        .unwrap_or(config.hue_rotate);
+
+    config.bias_hue = flag
+        .get_f32("--bias-hue")
+        .map(|v| v.rem_euclid(360.0))
+        .or(config.bias_hue);
+
+    config.bias_strength = flag
+        .get_f32("--bias-strength")
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(config.bias_strength);
+
+    config.filter_space = flag
+        .get_str("--filter-space")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.filter_space);
+
+    config.sort_by = flag
+        .get_str("--sort-by")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.sort_by);
+
+    config.order_by_dominance = flag
+        .get_opt_bool("--order-by-dominance")?
+        .unwrap_or(config.order_by_dominance);
+
+    config.normalize_lightness = flag
+        .get_opt_bool("--normalize-lightness")?
+        .unwrap_or(config.normalize_lightness);
+
+    config.target_lightness = flag
+        .get_f32("--target-lightness")
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(config.target_lightness);
 
-    if config.light {
-        std::mem::swap(&mut config.bg_color, &mut config.fg_color);
-    }
+    config.harmony = flag
+        .get_str("--harmony")
+        .map(|v| v.parse())
+        .transpose()?
+        .or(config.harmony);
 
-    let Some(image) = flag.get_str("-i") else {
-        log::info!("No image path specified");
-        log::info!("Exiting...");
-        return;
-    };
+    config.pin_bg_fg = flag
+        .get_opt_bool("--pin-bg-fg")?
+        .unwrap_or(config.pin_bg_fg);
+    config.pywal_order = flag
+        .get_opt_bool("--pywal-order")?
+        .unwrap_or(config.pywal_order);
 
-    let path = std::path::Path::new(&image);
-    let mut image = image.clone();
+    config.passthrough = flag
+        .get_opt_bool("--passthrough")?
+        .unwrap_or(config.passthrough);
 
-    if !path.exists() {
-        log::info!("path {} does not exist", &image);
-        log::info!("Exiting...");
-        return;
-    }
+    config.post_hook = flag.get_str("--hook").or(config.post_hook.take());
 
-    if path.is_dir() {
-        log::info!("Collecting files from {}", &image);
-        let images = collect_images(path);
+    if flag.get_opt_bool("--no-preview")?.unwrap_or(false) {
+        config.write_preview = false;
+    }
 
-        if images.is_empty() {
-            log::info!("No image files found at {}", &image);
-            log::info!("Exiting...");
-            return;
+    config.format = flag
+        .get_str("--format")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.format);
+
+    config.hex_case = flag
+        .get_str("--hex-case")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.hex_case);
+
+    config.color_notation = flag
+        .get_str("--color-notation")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.color_notation);
+
+    config.bg_mode = flag
+        .get_str("--bg-mode")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.bg_mode);
+
+    config.bg_from_corner = flag
+        .get_str("--bg-from-corner")
+        .map(|v| v.parse())
+        .transpose()?
+        .or(config.bg_from_corner);
+
+    config.fg_mode = flag
+        .get_str("--fg-mode")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.fg_mode);
+
+    config.skip_value = flag
+        .get_opt_bool("--skip-value")?
+        .unwrap_or(config.skip_value);
+
+    config.skip_saturation = flag
+        .get_opt_bool("--skip-saturation")?
+        .unwrap_or(config.skip_saturation);
+
+    config.skip_invert = flag
+        .get_opt_bool("--skip-invert")?
+        .unwrap_or(config.skip_invert);
+
+    config.skip_inclusive = flag
+        .get_opt_bool("--skip-inclusive")?
+        .unwrap_or(config.skip_inclusive);
+
+    config.clamp_value = flag
+        .get_opt_bool("--clamp-value")?
+        .unwrap_or(config.clamp_value);
+
+    config.clamp_saturation = flag
+        .get_opt_bool("--clamp-saturation")?
+        .unwrap_or(config.clamp_saturation);
+
+    config.clamp_mode = flag
+        .get_str("--clamp-mode")
+        .map(|v| v.parse())
+        .transpose()?
+        .unwrap_or(config.clamp_mode);
+
+    config.exclude_extremes = flag
+        .get_opt_bool("--exclude-extremes")?
+        .unwrap_or(config.exclude_extremes);
+
+    config.base_theme = flag.get_str("--base-theme").or(config.base_theme.take());
+
+    if let Some(indices) = flag.get_str("--merge-indices") {
+        config.merge_indices = indices
+            .split(',')
+            .map(|i| i.trim().parse::<usize>())
+            .collect::<Result<Vec<usize>, _>>()
+            .map_err(|e| format!("Invalid merge indices {}: {}", indices, e))?;
+
+        if config.merge_indices.iter().any(|&i| i > 15) {
+            return Err("--merge-indices must be between 0 and 15".into());
         }
-
-        let mut rand = rand::rng();
-        let index = rand.random_range(0..images.len());
-        image = images[index].to_string_lossy().to_string();
-
-        log::info!("Choosen image {}", image);
     }
 
-    let skip_cache = flag.get_bool("-c");
+    config.base_only = flag
+        .get_opt_bool("--base-only")?
+        .unwrap_or(config.base_only);
 
-    let rwal = rwal::Rwal {
-        backend: config.backend,
-        image_resize: (config.thumb_w, config.thumb_h),
+    config.linear_mix = flag
+        .get_opt_bool("--linear-mix")?
+        .unwrap_or(config.linear_mix);
 
-        bg_idx: config.bg_idx,
-        bg_color: config.bg_color,
-        bg_strength: config.bg_strength,
+    config.light = flag.get_opt_bool("-l")?.unwrap_or(config.light);
+    config.preserve_aspect = flag
+        .get_opt_bool("--preserve-aspect")?
+        .unwrap_or(config.preserve_aspect);
+    config.page = flag
+        .get_u32("--page")
+        .map(|v| v as usize)
+        .unwrap_or(config.page);
 
-        fg_idx: config.fg_idx,
-        fg_color: config.fg_color,
-        fg_strength: config.fg_strength,
+    config.respect_exif = flag
+        .get_opt_bool("--respect-exif")?
+        .unwrap_or(config.respect_exif);
 
-        clamp_saturation: config.clamp_saturation,
-        saturation_clamp: (config.clamp_saturation_min, config.clamp_saturation_max),
+    config.apply_simulation = flag
+        .get_opt_bool("--apply-simulation")?
+        .unwrap_or(config.apply_simulation);
 
-        skip_saturation: config.skip_saturation,
-        saturation_skip: (config.skip_saturation_min, config.skip_saturation_max),
+    Ok(())
+}
 
-        clamp_value: config.clamp_value,
-        value_clamp: (config.clamp_value_min, config.clamp_value_max),
+fn resolve_image(image: &str, pick_seed: Option<u64>) -> Result<String, String> {
+    if http::is_url(image) {
+        return Ok(image.to_string());
+    }
 
-        skip_value: config.skip_value,
-        value_skip: (config.skip_value_min, config.skip_value_max),
-    };
+    let path = std::path::Path::new(image);
 
-    if !crate::dirs::CACHE_DIR.exists() {
-        let _ = std::fs::create_dir_all(crate::dirs::CACHE_DIR.clone());
+    if !path.exists() {
+        return Err(format!("path {} does not exist", image));
     }
 
-    if !crate::dirs::PREV_COLORSCHEMES_DIR.exists() {
-        let _ = std::fs::create_dir_all(crate::dirs::PREV_COLORSCHEMES_DIR.clone());
+    if !path.is_dir() {
+        return Ok(image.to_string());
     }
 
-    if skip_cache {
-        log::info!("Skipping cache");
+    log::info!("Collecting files from {}", image);
+    let mut images = collect_images(path);
 
-        let colorscheme = match rwal.generate_colorscheme(&image) {
-            Ok(colorscheme) => colorscheme,
-            Err(e) => {
-                log::error!("Failed to get colorscheme: {:#?}", e);
-                return;
-            }
-        };
+    if images.is_empty() {
+        return Err(format!("No image files found at {}", image));
+    }
 
-        let _ = std::fs::write(
-            crate::dirs::HTML_PREVIEW_FILE.clone(),
-            colorscheme.html_preview(),
-        );
+    images.sort();
 
-        let _ = std::fs::write(
-            crate::dirs::CURRENT_COLORSCHEME_FILE.clone(),
-            colorscheme
-                .into_array()
-                .into_iter()
-                .map(rgb_to_hex)
-                .collect::<Vec<String>>()
-                .join("\n"),
-        );
+    let index = match pick_seed {
+        Some(seed) => StdRng::seed_from_u64(seed).random_range(0..images.len()),
+        None => rand::rng().random_range(0..images.len()),
+    };
+    let image = images[index].to_string_lossy().to_string();
 
-        return;
-    }
+    log::info!("Choosen image {}", image);
 
-    let name = image
-        .split("/")
-        .last()
-        .map(|p| p.to_string())
-        .unwrap_or(path.to_string_lossy().to_string());
-    let cache_name = format!("{}{}", config.cache_string(), name);
-    let mut cache_path = crate::dirs::PREV_COLORSCHEMES_DIR.clone();
-    cache_path.push(cache_name);
+    Ok(image)
+}
 
-    if cache_path.exists() {
-        log::info!("Cache exists");
-        let _ = std::fs::copy(&cache_path, crate::dirs::CURRENT_COLORSCHEME_FILE.clone());
-        log::info!("Exiting...");
-        return;
-    }
+fn parse_crop(value: &str) -> Result<(u32, u32, u32, u32), String> {
+    let parts = value
+        .split(',')
+        .map(|p| p.trim().parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|e| format!("Invalid crop {}: {}", value, e))?;
 
-    let colorscheme = match rwal.generate_colorscheme(&image) {
-        Ok(colorscheme) => colorscheme,
-        Err(e) => {
-            log::error!("Failed to get colorscheme: {:#?}", e);
-            return;
-        }
+    let [x, y, w, h] = parts[..] else {
+        return Err(format!("Invalid crop {}: expected x,y,w,h", value));
     };
 
-    let _ = std::fs::write(
-        crate::dirs::HTML_PREVIEW_FILE.clone(),
-        colorscheme.html_preview(),
-    );
+    Ok((x, y, w, h))
+}
 
+fn write_current(colorscheme: &rwal::Colorscheme) {
     let _ = std::fs::write(
-        &cache_path,
-        colorscheme
-            .into_array()
-            .into_iter()
-            .map(rgb_to_hex)
-            .collect::<Vec<String>>()
-            .join("\n"),
+        crate::dirs::CURRENT_COLORSCHEME_FILE.clone(),
+        colorscheme.to_hex_lines(),
     );
-
     let _ = std::fs::write(
-        crate::dirs::CURRENT_COLORSCHEME_FILE.clone(),
-        colorscheme
-            .into_array()
-            .into_iter()
-            .map(rgb_to_hex)
-            .collect::<Vec<String>>()
-            .join("\n"),
+        crate::dirs::CURSOR_FILE.clone(),
+        rgb_to_hex(colorscheme.cursor),
     );
 }
 
+fn read_cached_colorscheme(cache_path: &std::path::Path) -> Result<rwal::Colorscheme, String> {
+    let mut colorscheme = read_colorscheme(cache_path)?;
+
+    let cursor = std::fs::read_to_string(cache_info_path(cache_path))
+        .ok()
+        .and_then(|info| serde_json::from_str::<serde_json::Value>(&info).ok())
+        .and_then(|info| info["cursor"].as_str().map(hex_to_rgb));
+
+    if let Some(cursor) = cursor {
+        colorscheme.cursor = cursor?;
+    }
+
+    Ok(colorscheme)
+}
+
+fn read_colorscheme(path: &std::path::Path) -> Result<rwal::Colorscheme, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let lines = contents
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+
+    rwal::Colorscheme::from_hex_lines(&lines)
+}
+
 fn collect_images(path: &std::path::Path) -> Vec<std::path::PathBuf> {
     let mut result = Vec::new();
 
@@ -343,3 +1734,103 @@ fn collect_images(path: &std::path::Path) -> Vec<std::path::PathBuf> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Backend;
+
+    fn scheme(shift: u8) -> rwal::Colorscheme {
+        rwal::Colorscheme::from_array(std::array::from_fn(|i| {
+            let i = i as u8 * 16;
+            (i, i.wrapping_add(shift), 255 - i)
+        }))
+    }
+
+    fn layered(env: &[(&str, &str)], args: &[&str]) -> config::Config {
+        let mut config = config::Config {
+            backend: Backend::Colorthief,
+            ..config::Config::default()
+        };
+        apply_env(&mut config, |key| {
+            env.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        })
+        .unwrap();
+        let mut flag = flag::Flag::from_args(
+            std::iter::once("rwal")
+                .chain(args.iter().copied())
+                .map(String::from),
+        );
+        read_flags(&mut flag, &mut config).unwrap();
+        config
+    }
+
+    #[test]
+    fn env_overrides_config_and_flags_override_env() {
+        let config = layered(&[], &[]);
+        assert_eq!(config.backend, Backend::Colorthief);
+        assert!(!config.light);
+
+        let env = [("RWAL_BACKEND", "histogram"), ("RWAL_LIGHT", "true")];
+        let config = layered(&env, &[]);
+        assert_eq!(config.backend, Backend::Histogram);
+        assert!(config.light);
+
+        let config = layered(&env, &["--backend", "colorz", "-l=false"]);
+        assert_eq!(config.backend, Backend::ColorZ);
+        assert!(!config.light);
+    }
+
+    #[test]
+    fn empty_env_is_ignored_and_invalid_env_fails() {
+        let config = layered(&[("RWAL_BACKEND", "")], &[]);
+        assert_eq!(config.backend, Backend::Colorthief);
+
+        let mut config = config::Config::default();
+        let env = |key: &str| (key == "RWAL_LIGHT").then(|| "maybe".to_string());
+        assert!(apply_env(&mut config, env).is_err());
+    }
+
+    #[test]
+    fn pick_seed_is_deterministic() {
+        let dir = std::env::temp_dir().join(format!("rwal-pick-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.png", "b.jpg", "c.webp", "d.png", "e.jpeg", "notes.txt"] {
+            std::fs::write(dir.join(name), []).unwrap();
+        }
+        let dir_str = dir.to_str().unwrap();
+
+        let picks = (0..8)
+            .map(|seed| resolve_image(dir_str, Some(seed)).unwrap())
+            .collect::<Vec<_>>();
+        let repicks = (0..8)
+            .map(|seed| resolve_image(dir_str, Some(seed)).unwrap())
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(picks, repicks);
+        assert!(picks.iter().all(|pick| !pick.ends_with("notes.txt")));
+        assert!(picks.iter().any(|pick| *pick != picks[0]));
+    }
+
+    #[test]
+    fn force_overwrites_the_cache() {
+        let dir = std::env::temp_dir().join(format!("rwal-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache_path = dir.join("cached");
+        let config = config::Config::default();
+        let images = ["image.png".to_string()];
+
+        write_cache(&cache_path, &config, &images, &[], &[], &scheme(0));
+        assert_eq!(exact_cache(Some(&cache_path), false), Some(&cache_path));
+        assert_eq!(exact_cache(Some(&cache_path), true), None);
+
+        write_cache(&cache_path, &config, &images, &[], &[], &scheme(1));
+        let cached = read_cached_colorscheme(&cache_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cached.into_array(), scheme(1).into_array());
+    }
+}
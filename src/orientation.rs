@@ -0,0 +1,30 @@
+use image::{DynamicImage, metadata::Orientation};
+
+pub fn apply(mut img: DynamicImage, bytes: &[u8]) -> DynamicImage {
+    match read(bytes).and_then(Orientation::from_exif) {
+        Some(Orientation::NoTransforms) | None => img,
+        Some(orientation) => {
+            log::debug!("Applying EXIF orientation {:?}", orientation);
+            img.apply_orientation(orientation);
+            img
+        }
+    }
+}
+
+#[cfg(feature = "exif")]
+fn read(bytes: &[u8]) -> Option<u8> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(bytes))
+        .ok()?;
+
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+        .and_then(|v| u8::try_from(v).ok())
+}
+
+#[cfg(not(feature = "exif"))]
+fn read(_: &[u8]) -> Option<u8> {
+    log::warn!("Cannot read EXIF orientation: rwal was built without the exif feature");
+    None
+}
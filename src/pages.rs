@@ -0,0 +1,116 @@
+use std::io::BufRead;
+use std::io::Seek;
+
+use image::AnimationDecoder;
+use image::DynamicImage;
+use image::ImageBuffer;
+use image::ImageFormat;
+use image::ImageReader;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+
+pub fn decode<R: BufRead + Seek>(reader: R, page: usize) -> Result<DynamicImage, String> {
+    let reader = ImageReader::new(reader)
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let format = reader.format();
+    let reader = reader.into_inner();
+
+    match format {
+        Some(ImageFormat::Tiff) => decode_tiff(reader, page),
+        Some(ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(reader).map_err(|e| e.to_string())?;
+            decode_frame(decoder, page)
+        }
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader).map_err(|e| e.to_string())?;
+            if !decoder.is_apng().map_err(|e| e.to_string())? {
+                return Err(out_of_range(page, 1));
+            }
+            decode_frame(decoder.apng().map_err(|e| e.to_string())?, page)
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader).map_err(|e| e.to_string())?;
+            if !decoder.has_animation() {
+                return Err(out_of_range(page, 1));
+            }
+            decode_frame(decoder, page)
+        }
+        Some(format) => Err(format!("{:?} images have a single page", format)),
+        None => Err("Unknown image format".to_string()),
+    }
+}
+
+fn decode_frame<'a, D: AnimationDecoder<'a>>(
+    decoder: D,
+    page: usize,
+) -> Result<DynamicImage, String> {
+    let mut frames = decoder.into_frames();
+
+    for skipped in 0..page {
+        match frames.next() {
+            Some(frame) => {
+                frame.map_err(|e| e.to_string())?;
+            }
+            None => return Err(out_of_range(page, skipped)),
+        }
+    }
+
+    match frames.next() {
+        Some(frame) => Ok(DynamicImage::ImageRgba8(
+            frame.map_err(|e| e.to_string())?.into_buffer(),
+        )),
+        None => Err(out_of_range(page, page)),
+    }
+}
+
+fn decode_tiff<R: BufRead + Seek>(reader: R, page: usize) -> Result<DynamicImage, String> {
+    use tiff::ColorType;
+    use tiff::decoder::DecodingResult;
+
+    let mut decoder = tiff::decoder::Decoder::new(reader).map_err(|e| e.to_string())?;
+
+    for skipped in 0..page {
+        if !decoder.more_images() {
+            return Err(out_of_range(page, skipped + 1));
+        }
+        decoder.next_image().map_err(|e| e.to_string())?;
+    }
+
+    let (width, height) = decoder.dimensions().map_err(|e| e.to_string())?;
+    let colortype = decoder.colortype().map_err(|e| e.to_string())?;
+
+    let data = match decoder.read_image().map_err(|e| e.to_string())? {
+        DecodingResult::U8(data) => data,
+        DecodingResult::U16(data) => data.into_iter().map(|v| (v >> 8) as u8).collect(),
+        _ => return Err("Unsupported TIFF sample format".to_string()),
+    };
+
+    let img = match colortype {
+        ColorType::Gray(8 | 16) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        ColorType::GrayA(8 | 16) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        ColorType::RGB(8 | 16) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        ColorType::RGBA(8 | 16) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        _ => return Err(format!("Unsupported TIFF color type {:?}", colortype)),
+    };
+
+    img.ok_or_else(|| "Malformed TIFF page".to_string())
+}
+
+fn out_of_range(page: usize, pages: usize) -> String {
+    format!(
+        "Page {} is out of range, the image has {} page{}",
+        page,
+        pages,
+        if pages == 1 { "" } else { "s" }
+    )
+}
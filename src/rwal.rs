@@ -1,22 +1,74 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::Duration;
+use std::time::Instant;
+
+use image::DynamicImage;
 use image::RgbImage;
 use palette::FromColor;
+use palette::Hsl;
 use palette::Hsv;
+use palette::IntoColor;
+use palette::Lab;
+use palette::Lch;
+use palette::LinSrgb;
+use palette::Oklch;
+use palette::RgbHue;
 use palette::Srgb;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::backends::Backend;
 use crate::backends::RwalBackend;
+use crate::backends::WeightedColor;
+use crate::config::Config;
+use crate::config::hex_to_rgb;
+use crate::config::rgb_to_css;
+use crate::config::rgb_to_hex;
+use crate::harmony::Harmony;
+
+/// Number of palette colors `build_colorscheme` reads.
+pub const PALETTE_SIZE: usize = 8;
 
+/// A decoded image along with the path it was loaded from.
+pub type Decoded = (String, DynamicImage);
+
+#[derive(Clone)]
 pub struct Rwal {
     pub backend: Backend,
+    pub colorthief_quality: u8,
+    pub kmeans_runs: u32,
     pub image_resize: (u32, u32),
+    pub image_scale: Option<f32>,
+    pub max_pixels: Option<u32>,
+    pub preserve_aspect: bool,
+    pub respect_exif: bool,
+    pub page: usize,
+    pub center_weight: f32,
+    pub edge_weight: f32,
+    pub sample_corners: bool,
+    pub corner_margin: f32,
+    pub crop: Option<(u32, u32, u32, u32)>,
+    pub crop_center: Option<f32>,
 
     pub bg_idx: usize,
     pub bg_color: (u8, u8, u8),
     pub bg_strength: u8,
+    pub bg_mode: MixMode,
+    pub bg_from_corner: Option<Region>,
 
     pub fg_idx: usize,
+    pub auto_bg_fg: bool,
     pub fg_strength: u8,
     pub fg_color: (u8, u8, u8),
+    pub fg_mode: MixMode,
+
+    pub cursor_idx: usize,
+    pub cursor_color: Option<(u8, u8, u8)>,
 
     pub clamp_saturation: bool,
     pub saturation_clamp: (f32, f32),
@@ -26,13 +78,270 @@ pub struct Rwal {
 
     pub clamp_value: bool,
     pub value_clamp: (f32, f32),
+    pub clamp_mode: ClampMode,
 
     pub skip_value: bool,
+    pub skip_inclusive: bool,
+    pub skip_invert: bool,
     pub value_skip: (f32, f32),
+
+    pub filter_space: FilterSpace,
+
+    pub exclude_extremes: bool,
+    pub extremes_exclude: (f32, f32),
+
+    pub min_distance: Option<f32>,
+    pub threshold_colors: Option<f32>,
+    pub sort_by: SortBy,
+    pub order_by_dominance: bool,
+    pub normalize_lightness: bool,
+    pub target_lightness: f32,
+    pub harmony: Option<Harmony>,
+    pub pin_bg_fg: bool,
+    pub pywal_order: bool,
+    pub light: bool,
+    pub passthrough: bool,
+    pub timeout: Option<u64>,
+    pub temperature: Option<f32>,
+    pub hue_rotate: f32,
+    pub bias_hue: Option<f32>,
+    pub bias_strength: f32,
+    pub max_retries: u32,
+    pub min_colors: usize,
+    pub seed: u64,
+    pub sample_stride: usize,
+    pub sample_random: Option<usize>,
+    pub linear_mix: bool,
+    pub light_map: [usize; 8],
+    pub base_only: bool,
+}
+
+impl From<&Config> for Rwal {
+    fn from(config: &Config) -> Self {
+        Rwal {
+            backend: config.backend,
+            colorthief_quality: config.colorthief_quality,
+            kmeans_runs: config.kmeans_runs,
+            image_resize: (config.thumb_w, config.thumb_h),
+            image_scale: config.thumb_scale,
+            max_pixels: config.max_pixels,
+            preserve_aspect: config.preserve_aspect,
+            respect_exif: config.respect_exif,
+            page: config.page,
+            center_weight: config.center_weight,
+            edge_weight: config.edge_weight,
+            sample_corners: config.sample_corners,
+            corner_margin: config.corner_margin,
+            crop: config.crop,
+            crop_center: config.crop_center,
+
+            bg_idx: config.bg_idx,
+            bg_color: config.bg_color,
+            bg_strength: config.bg_strength,
+            bg_mode: config.bg_mode,
+            bg_from_corner: config.bg_from_corner,
+
+            fg_idx: config.fg_idx,
+            auto_bg_fg: config.auto_bg_fg,
+            fg_color: config.fg_color,
+            fg_strength: config.fg_strength,
+            fg_mode: config.fg_mode,
+            linear_mix: config.linear_mix,
+            cursor_idx: config.cursor_idx,
+            cursor_color: config.cursor_color,
+            light_map: config.light_map,
+            base_only: config.base_only,
+
+            clamp_saturation: config.clamp_saturation,
+            saturation_clamp: (config.clamp_saturation_min, config.clamp_saturation_max),
+
+            skip_saturation: config.skip_saturation,
+            saturation_skip: (config.skip_saturation_min, config.skip_saturation_max),
+
+            clamp_value: config.clamp_value,
+            value_clamp: (config.clamp_value_min, config.clamp_value_max),
+            clamp_mode: config.clamp_mode,
+
+            skip_value: config.skip_value,
+            skip_inclusive: config.skip_inclusive,
+            skip_invert: config.skip_invert,
+            value_skip: (config.skip_value_min, config.skip_value_max),
+
+            filter_space: config.filter_space,
+
+            exclude_extremes: config.exclude_extremes,
+            extremes_exclude: (config.exclude_dark, config.exclude_light),
+
+            min_distance: config.min_distance,
+            threshold_colors: config.threshold_colors,
+            sort_by: config.sort_by,
+            order_by_dominance: config.order_by_dominance,
+            normalize_lightness: config.normalize_lightness,
+            target_lightness: config.target_lightness,
+            harmony: config.harmony,
+            pin_bg_fg: config.pin_bg_fg,
+            pywal_order: config.pywal_order,
+            light: config.light,
+            passthrough: config.passthrough,
+            timeout: config.timeout,
+            max_retries: config.max_retries,
+            min_colors: PALETTE_SIZE,
+            seed: config.seed,
+            sample_stride: config.sample_stride as usize,
+            sample_random: config.sample_random.map(|v| v as usize),
+            temperature: config.temperature,
+            hue_rotate: config.hue_rotate,
+            bias_hue: config.bias_hue,
+            bias_strength: config.bias_strength,
+        }
+    }
+}
+
+pub struct RwalBuilder {
+    rwal: Rwal,
+}
+
+#[allow(dead_code)]
+impl RwalBuilder {
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.rwal.backend = backend;
+        self
+    }
+
+    pub fn thumb(mut self, w: u32, h: u32) -> Self {
+        self.rwal.image_resize = (w, h);
+        self
+    }
+
+    pub fn thumb_scale(mut self, scale: f32) -> Self {
+        self.rwal.image_scale = Some(scale);
+        self
+    }
+
+    pub fn max_pixels(mut self, max_pixels: u32) -> Self {
+        self.rwal.max_pixels = Some(max_pixels);
+        self
+    }
+
+    pub fn preserve_aspect(mut self, preserve_aspect: bool) -> Self {
+        self.rwal.preserve_aspect = preserve_aspect;
+        self
+    }
+
+    pub fn respect_exif(mut self, respect_exif: bool) -> Self {
+        self.rwal.respect_exif = respect_exif;
+        self
+    }
+
+    pub fn center_weight(mut self, center_weight: f32) -> Self {
+        self.rwal.center_weight = center_weight;
+        self
+    }
+
+    pub fn edge_weight(mut self, edge_weight: f32) -> Self {
+        self.rwal.edge_weight = edge_weight;
+        self
+    }
+
+    pub fn crop(mut self, x: u32, y: u32, w: u32, h: u32) -> Self {
+        self.rwal.crop = Some((x, y, w, h));
+        self
+    }
+
+    pub fn bg(mut self, color: (u8, u8, u8), idx: usize, strength: u8) -> Self {
+        self.rwal.bg_color = color;
+        self.rwal.bg_idx = idx;
+        self.rwal.bg_strength = strength;
+        self
+    }
+
+    pub fn fg(mut self, color: (u8, u8, u8), idx: usize, strength: u8) -> Self {
+        self.rwal.fg_color = color;
+        self.rwal.fg_idx = idx;
+        self.rwal.fg_strength = strength;
+        self
+    }
+
+    pub fn clamp_saturation(mut self, min: f32, max: f32) -> Self {
+        self.rwal.clamp_saturation = true;
+        self.rwal.saturation_clamp = (min, max);
+        self
+    }
+
+    pub fn clamp_value(mut self, min: f32, max: f32) -> Self {
+        self.rwal.clamp_value = true;
+        self.rwal.value_clamp = (min, max);
+        self
+    }
+
+    pub fn skip_saturation(mut self, min: f32, max: f32) -> Self {
+        self.rwal.skip_saturation = true;
+        self.rwal.saturation_skip = (min, max);
+        self
+    }
+
+    pub fn skip_value(mut self, min: f32, max: f32) -> Self {
+        self.rwal.skip_value = true;
+        self.rwal.value_skip = (min, max);
+        self
+    }
+
+    pub fn skip_inclusive(mut self, skip_inclusive: bool) -> Self {
+        self.rwal.skip_inclusive = skip_inclusive;
+        self
+    }
+
+    pub fn skip_invert(mut self, skip_invert: bool) -> Self {
+        self.rwal.skip_invert = skip_invert;
+        self
+    }
+
+    pub fn no_clamp(mut self) -> Self {
+        self.rwal.clamp_saturation = false;
+        self.rwal.clamp_value = false;
+        self
+    }
+
+    pub fn no_skip(mut self) -> Self {
+        self.rwal.skip_saturation = false;
+        self.rwal.skip_value = false;
+        self
+    }
+
+    pub fn sort_by(mut self, sort_by: SortBy) -> Self {
+        self.rwal.sort_by = sort_by;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rwal.seed = seed;
+        self
+    }
+
+    pub fn timeout(mut self, secs: u64) -> Self {
+        self.rwal.timeout = Some(secs);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.rwal.max_retries = max_retries;
+        self
+    }
+
+    pub fn build(self) -> Rwal {
+        self.rwal
+    }
 }
 
 impl Rwal {
-    fn prepare_colors(&self, image: RgbImage) -> Vec<(u8, u8, u8)> {
+    #[allow(dead_code)]
+    pub fn builder() -> RwalBuilder {
+        RwalBuilder {
+            rwal: Rwal::from(&Config::default()),
+        }
+    }
+
+    pub fn prepare_colors(&self, image: RgbImage) -> Vec<(u8, u8, u8)> {
         let s_min = self.saturation_clamp.0;
         let s_max = self.saturation_clamp.1;
         let v_min = self.value_clamp.0;
@@ -43,208 +352,2485 @@ impl Rwal {
         let v_skip_min = self.value_skip.0;
         let v_skip_max = self.value_skip.1;
 
-        image
-            .pixels()
-            .map(|p| {
-                let srgb_u8 = Srgb::new(p[0], p[1], p[2]);
-                let srgb_f32: Srgb<f32> = srgb_u8.into_format();
+        let (width, height) = image.dimensions();
 
-                Hsv::from_color(srgb_f32)
-            })
-            .filter(|c| {
-                if !self.skip_saturation {
-                    true
+        let margin_x = (width as f32 * self.corner_margin / 100.0).ceil() as u32;
+        let margin_y = (height as f32 * self.corner_margin / 100.0).ceil() as u32;
+        let near_edge = |x: u32, y: u32| {
+            x < margin_x || x + margin_x >= width || y < margin_y || y + margin_y >= height
+        };
+
+        let gradient = if self.edge_weight > 0.0 {
+            sobel(&image)
+        } else {
+            Vec::new()
+        };
+
+        let mut sampled: Vec<_> = image
+            .enumerate_pixels()
+            .filter(|(x, y, _)| !self.sample_corners || near_edge(*x, *y))
+            .step_by(self.sample_stride.max(1))
+            .collect();
+
+        if let Some(count) = self.sample_random
+            && count < sampled.len()
+        {
+            let mut rng = StdRng::seed_from_u64(self.seed);
+            let mut indices = rand::seq::index::sample(&mut rng, sampled.len(), count).into_vec();
+            indices.sort_unstable();
+            sampled = indices.into_iter().map(|i| sampled[i]).collect();
+        }
+
+        let mut converted: PixelMap<Hsv> = PixelMap::default();
+        let pixels: Vec<Hsv> = sampled
+            .into_iter()
+            .flat_map(|(x, y, p)| {
+                let mut copies = if self.center_weight > 0.0 {
+                    let dx = (x as f32 + 0.5) / width as f32 - 0.5;
+                    let dy = (y as f32 + 0.5) / height as f32 - 0.5;
+                    let falloff = (-(dx * dx + dy * dy) / (2.0 * 0.25 * 0.25)).exp();
+                    1 + (self.center_weight * 4.0 * falloff).round() as usize
                 } else {
-                    c.saturation > s_skip_min && c.saturation < s_skip_max
+                    1
+                };
+
+                if let Some(magnitude) = gradient.get((y * width + x) as usize) {
+                    copies += (self.edge_weight * 4.0 * magnitude).round() as usize;
                 }
+
+                std::iter::repeat_n(p, copies)
             })
-            .filter(|c| {
-                if !self.skip_value {
-                    true
-                } else {
-                    c.value > v_skip_min && c.value < v_skip_max
-                }
+            .map(|p| {
+                *converted.entry(pack_rgb(p)).or_insert_with(|| {
+                    let srgb_u8 = Srgb::new(p[0], p[1], p[2]);
+                    let srgb_f32: Srgb<f32> = srgb_u8.into_format();
+
+                    Hsv::from_color(srgb_f32)
+                })
             })
-            .map(|c| {
-                let mut hsv: Hsv = c;
+            .collect();
+
+        let pixels = if self.exclude_extremes {
+            let (dark, light) = self.extremes_exclude;
+            let kept: Vec<Hsv> = pixels
+                .iter()
+                .filter(|c| c.value >= dark && c.value * (1.0 - c.saturation) <= light)
+                .copied()
+                .collect();
+
+            if kept.is_empty() {
+                log::warn!("Excluding extremes left no pixels, keeping them");
+                pixels
+            } else {
+                kept
+            }
+        } else {
+            pixels
+        };
+
+        let space = self.filter_space;
+        let in_range = |v: f32, min: f32, max: f32| {
+            if self.skip_inclusive {
+                v >= min && v <= max
+            } else {
+                v > min && v < max
+            }
+        };
+
+        pixels
+            .into_iter()
+            .filter_map(|c| {
+                let (mut saturation, mut value) = space.axes(c);
+
+                if self.skip_saturation
+                    && in_range(saturation, s_skip_min, s_skip_max) == self.skip_invert
+                {
+                    return None;
+                }
+                if self.skip_value && in_range(value, v_skip_min, v_skip_max) == self.skip_invert {
+                    return None;
+                }
 
                 if self.clamp_saturation {
-                    hsv.saturation = hsv.saturation.clamp(s_min, s_max);
+                    saturation = self.clamp_mode.apply(saturation, s_min, s_max);
                 }
                 if self.clamp_value {
-                    hsv.value = hsv.value.clamp(v_min, v_max);
+                    value = self.clamp_mode.apply(value, v_min, v_max);
                 }
 
-                let clamped_rgb: Srgb<f32> = Srgb::from_color(hsv);
+                let clamped_rgb = space.with_axes(c, saturation, value);
                 let clamped_rgb_u8: Srgb<u8> = clamped_rgb.into_format();
 
-                (
+                Some((
                     clamped_rgb_u8.red,
                     clamped_rgb_u8.green,
                     clamped_rgb_u8.blue,
-                )
+                ))
             })
             .collect()
     }
 
-    pub fn generate_colorscheme(&self, path: &str) -> Result<Colorscheme, &'static str> {
-        let img = image::open(path).map_err(|_| "Failed to open image")?;
-        let img = img.resize_exact(
-            self.image_resize.0,
-            self.image_resize.1,
-            image::imageops::Nearest,
-        );
+    fn crop_rect(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Option<(u32, u32, u32, u32)>, &'static str> {
+        let (x, y, w, h) = if let Some(crop) = self.crop {
+            crop
+        } else if let Some(pct) = self.crop_center {
+            let w = ((width as f32 * pct / 100.0) as u32).max(1);
+            let h = ((height as f32 * pct / 100.0) as u32).max(1);
+            ((width - w) / 2, (height - h) / 2, w, h)
+        } else {
+            return Ok(None);
+        };
+
+        if w == 0 || h == 0 || x.saturating_add(w) > width || y.saturating_add(h) > height {
+            return Err("Crop rectangle does not fit within the image");
+        }
+
+        Ok(Some((x, y, w, h)))
+    }
+
+    fn load_colors(&self, path: &str) -> Result<Vec<(u8, u8, u8)>, &'static str> {
+        let thumb = self.load_thumb(path)?;
+
+        let start = Instant::now();
+        let colors = self.prepare_colors(thumb);
+        log::debug!("Prepared {} in {}ms", path, start.elapsed().as_millis());
+
+        Ok(colors)
+    }
+
+    fn load_thumb(&self, path: &str) -> Result<RgbImage, &'static str> {
+        self.thumbnail(path, &self.decode(path)?)
+    }
+
+    fn decode(&self, path: &str) -> Result<DynamicImage, &'static str> {
+        let start = Instant::now();
+        let url = crate::http::is_url(path);
+        let img = if url || self.respect_exif || self.page > 0 {
+            let bytes = if url {
+                crate::http::fetch(path).map_err(|e| {
+                    log::error!("{}", e);
+                    "Failed to download image"
+                })?
+            } else {
+                std::fs::read(path).map_err(|_| "Failed to open image")?
+            };
 
-        let colors = self.prepare_colors(img.to_rgb8());
+            let img = if self.page > 0 {
+                crate::pages::decode(std::io::Cursor::new(&bytes), self.page).map_err(|e| {
+                    log::error!("{}: {}", path, e);
+                    "Failed to decode page"
+                })?
+            } else if url {
+                image::load_from_memory(&bytes).map_err(|_| "Downloaded file is not an image")?
+            } else {
+                image::load_from_memory(&bytes).map_err(|_| "Failed to open image")?
+            };
 
-        let Some(palette) = self.backend.generate_palette(&colors, 8) else {
-            return Err("Failed to generate palette");
+            if self.respect_exif {
+                crate::orientation::apply(img, &bytes)
+            } else {
+                img
+            }
+        } else {
+            image::open(path).map_err(|_| "Failed to open image")?
         };
+        log::debug!("Decoded {} in {}ms", path, start.elapsed().as_millis());
 
-        let palette = sort_by_hue(&palette);
+        Ok(img)
+    }
 
-        if palette.len() < 8 {
-            return Err("Not enough colors generated");
-        }
+    fn thumbnail(&self, path: &str, img: &DynamicImage) -> Result<RgbImage, &'static str> {
+        let start = Instant::now();
+        let cropped;
+        let img = match self.crop_rect(img.width(), img.height())? {
+            Some((x, y, w, h)) => {
+                cropped = img.crop_imm(x, y, w, h);
+                &cropped
+            }
+            None => img,
+        };
+
+        let (w, h) = match (self.max_pixels, self.image_scale) {
+            (Some(max_pixels), _) => {
+                let pixels = img.width() as f64 * img.height() as f64;
+                let scale = (max_pixels as f64 / pixels).sqrt().min(1.0);
+                (
+                    ((img.width() as f64 * scale) as u32).max(1),
+                    ((img.height() as f64 * scale) as u32).max(1),
+                )
+            }
+            (None, Some(pct)) => (
+                ((img.width() as f32 * pct / 100.0) as u32).max(1),
+                ((img.height() as f32 * pct / 100.0) as u32).max(1),
+            ),
+            (None, None) => self.image_resize,
+        };
+
+        let img = if self.preserve_aspect {
+            img.resize(w, h, image::imageops::Nearest)
+        } else {
+            img.resize_exact(w, h, image::imageops::Nearest)
+        };
+        log::debug!("Resized {} in {}ms", path, start.elapsed().as_millis());
+
+        Ok(img.to_rgb8())
+    }
+
+    pub fn count_distinct(&self, path: &str) -> Result<(usize, usize, usize), &'static str> {
+        let thumb = self.load_thumb(path)?;
+        let pixels = thumb.width() as usize * thumb.height() as usize;
+
+        let colors = self.prepare_colors(thumb);
+        let distinct = colors.iter().collect::<HashSet<_>>().len();
+
+        Ok((pixels, colors.len(), distinct))
+    }
+
+    fn run_backend<T: Send + 'static>(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        seed: u64,
+        job: BackendJob<T>,
+    ) -> Option<T> {
+        let Some(timeout) = self.timeout else {
+            let backend = self
+                .backend
+                .build(self.colorthief_quality, self.kmeans_runs);
+            return job(&*backend, colors, count, seed);
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let backend = self.backend;
+        let worker = backend.build(self.colorthief_quality, self.kmeans_runs);
+        let worker_colors = colors.to_vec();
 
-        let palette = sort_by_hue(&palette);
+        std::thread::spawn(move || {
+            let _ = tx.send(job(&*worker, &worker_colors, count, seed));
+        });
 
-        let bg = mix_colors(self.bg_color, palette[self.bg_idx], self.bg_strength);
-        let fg = mix_colors(self.fg_color, palette[self.fg_idx], self.fg_strength);
+        match rx.recv_timeout(Duration::from_secs(timeout)) {
+            Ok(result) => result,
+            Err(_) => {
+                log::warn!(
+                    "{} backend timed out after {}s, falling back to {}",
+                    backend,
+                    timeout,
+                    Backend::Colorthief
+                );
+                let fallback = Backend::Colorthief.build(self.colorthief_quality, self.kmeans_runs);
+                job(&*fallback, colors, count, seed)
+            }
+        }
+    }
 
-        Ok(Colorscheme {
-            t0: bg,
-            t1: palette[1],
-            t2: palette[2],
-            t3: palette[3],
-            t4: palette[4],
-            t5: palette[5],
-            t6: palette[6],
-            t7: fg,
-            t8: mix_colors(bg, (255, 255, 255), 10),
-            t9: mix_colors(palette[1], (255, 255, 255), 30),
-            t10: mix_colors(palette[2], (255, 255, 255), 30),
-            t11: mix_colors(palette[3], (255, 255, 255), 30),
-            t12: mix_colors(palette[4], (255, 255, 255), 30),
-            t13: mix_colors(palette[5], (255, 255, 255), 30),
-            t14: mix_colors(palette[6], (255, 255, 255), 30),
-            t15: mix_colors(fg, (255, 255, 255), 10),
+    fn generate_palette(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        seed: u64,
+    ) -> Option<Vec<(u8, u8, u8)>> {
+        self.run_backend(colors, count, seed, |backend, colors, count, seed| {
+            backend.generate_palette_seeded(colors, count, seed)
         })
     }
-}
 
-#[derive(Clone, Copy)]
-pub struct Colorscheme {
-    pub t0: (u8, u8, u8),
-    pub t1: (u8, u8, u8),
-    pub t2: (u8, u8, u8),
-    pub t3: (u8, u8, u8),
-    pub t4: (u8, u8, u8),
-    pub t5: (u8, u8, u8),
-    pub t6: (u8, u8, u8),
-    pub t7: (u8, u8, u8),
+    fn generate_palette_weighted(
+        &self,
+        colors: &[(u8, u8, u8)],
+        count: usize,
+        seed: u64,
+    ) -> Option<Vec<WeightedColor>> {
+        self.run_backend(colors, count, seed, |backend, colors, count, seed| {
+            backend.generate_palette_weighted(colors, count, seed)
+        })
+    }
 
-    pub t8: (u8, u8, u8),
-    pub t9: (u8, u8, u8),
-    pub t10: (u8, u8, u8),
-    pub t11: (u8, u8, u8),
-    pub t12: (u8, u8, u8),
-    pub t13: (u8, u8, u8),
-    pub t14: (u8, u8, u8),
-    pub t15: (u8, u8, u8),
-}
+    fn retry<T>(
+        &self,
+        mut generate: impl FnMut(u64) -> Option<T>,
+        palette: impl Fn(&T) -> Vec<(u8, u8, u8)>,
+    ) -> Result<T, String> {
+        let start = Instant::now();
+        let mut generated = false;
 
-impl Colorscheme {
-    pub fn html_preview(&self) -> String {
-        const DIV: &str = include_str!("./div.html");
-        const PREV: &str = include_str!("./preview.html");
+        for attempt in 0..=self.max_retries as u64 {
+            let Some(candidate) = generate(self.seed.wrapping_add(attempt)) else {
+                log::debug!("Attempt {} failed to generate a palette", attempt + 1);
+                continue;
+            };
 
-        let mut dark_divs = Vec::new();
-        let mut light_divs = Vec::new();
+            let distinct = palette(&candidate).iter().collect::<HashSet<_>>().len();
+            if distinct >= self.min_colors {
+                log::debug!("Generated palette in {}ms", start.elapsed().as_millis());
+                return Ok(candidate);
+            }
 
-        let bg = self.t0;
-        let fg = self.t7;
+            log::debug!(
+                "Attempt {} produced {} distinct colors, retrying",
+                attempt + 1,
+                distinct
+            );
+            generated = true;
+        }
 
-        let dark = [
-            self.t0, self.t1, self.t2, self.t3, self.t4, self.t5, self.t6, self.t7,
-        ];
-        let light = [
-            self.t8, self.t9, self.t10, self.t11, self.t12, self.t13, self.t14, self.t15,
-        ];
+        if generated {
+            return Err(format!(
+                "Failed to generate palette with {} distinct colors after {} retries",
+                self.min_colors, self.max_retries
+            ));
+        }
 
-        for c in dark {
-            let div = DIV
-                .replace("R", &c.0.to_string())
-                .replace("G", &c.1.to_string())
-                .replace("B", &c.2.to_string());
-            dark_divs.push(div);
+        Err("Failed to generate palette".into())
+    }
+
+    pub fn load_images(&self, paths: &[String]) -> Result<Vec<Decoded>, &'static str> {
+        let mut images = Vec::new();
+
+        for path in paths {
+            match self.decode(path) {
+                Ok(img) => images.push((path.clone(), img)),
+                Err(e) if paths.len() > 1 => log::warn!("{}: {}, skipping", path, e),
+                Err(e) => return Err(e),
+            }
         }
 
-        for c in light {
-            let div = DIV
-                .replace("R", &c.0.to_string())
-                .replace("G", &c.1.to_string())
-                .replace("B", &c.2.to_string());
-            light_divs.push(div);
+        if images.is_empty() {
+            return Err("Failed to open image");
         }
 
-        PREV.replace("{{DDIV}}", &dark_divs.join(""))
-            .replace("{{LDIV}}", &light_divs.join(""))
-            .replace("{{BR}}", &bg.0.to_string())
-            .replace("{{BG}}", &bg.1.to_string())
-            .replace("{{BB}}", &bg.2.to_string())
-            .replace("{{FR}}", &fg.0.to_string())
-            .replace("{{FG}}", &fg.1.to_string())
-            .replace("{{FB}}", &fg.2.to_string())
+        Ok(images)
     }
 
-    pub fn into_array(self) -> [(u8, u8, u8); 16] {
-        [
-            self.t0, self.t1, self.t2, self.t3, self.t4, self.t5, self.t6, self.t7, self.t8,
-            self.t9, self.t10, self.t11, self.t12, self.t13, self.t14, self.t15,
-        ]
+    fn load_all_thumbs(&self, images: &[Decoded]) -> Result<Vec<RgbImage>, &'static str> {
+        let mut thumbs = Vec::new();
+
+        for (path, img) in images {
+            match self.thumbnail(path, img) {
+                Ok(thumb) => thumbs.push(thumb),
+                Err(e) if images.len() > 1 => log::warn!("{}: {}, skipping", path, e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if thumbs.is_empty() {
+            return Err("Failed to open image");
+        }
+
+        Ok(thumbs)
     }
-}
 
-fn sort_by_hue(palette: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
-    let mut hsv_palette: Vec<Hsv> = palette
-        .iter()
-        .map(|c| {
-            let srgb_u8 = Srgb::new(c.0, c.1, c.2);
-            let srgb_f32: Srgb<f32> = srgb_u8.into_format();
-            Hsv::from_color(srgb_f32)
-        })
-        .collect();
+    fn prepare_all_colors(&self, thumbs: Vec<RgbImage>) -> Vec<(u8, u8, u8)> {
+        let start = Instant::now();
+        let colors = thumbs
+            .into_iter()
+            .flat_map(|thumb| self.prepare_colors(thumb))
+            .collect();
+        log::debug!("Prepared colors in {}ms", start.elapsed().as_millis());
 
-    hsv_palette.sort_by(|f, s| {
-        let f_hue: f32 = f.hue.into();
-        let s_hue: f32 = s.hue.into();
-        f_hue.partial_cmp(&s_hue).unwrap()
-    });
+        colors
+    }
 
-    hsv_palette
-        .into_iter()
-        .map(|hsv| {
-            let rgb: Srgb<f32> = Srgb::from_color(hsv);
-            let rgb_u8: Srgb<u8> = rgb.into_format();
-            (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
-        })
-        .collect()
-}
+    fn load_all_colors(&self, paths: &[String]) -> Result<Vec<(u8, u8, u8)>, &'static str> {
+        Ok(self.prepare_all_colors(self.load_all_thumbs(&self.load_images(paths)?)?))
+    }
 
-fn mix_colors(f: (u8, u8, u8), s: (u8, u8, u8), pos: u8) -> (u8, u8, u8) {
-    let pos = pos.clamp(0, 100) as u16;
+    pub fn palette_weights(&self, paths: &[String]) -> Result<Vec<WeightedColor>, &'static str> {
+        let colors = self.load_all_colors(paths)?;
 
-    let interpolate = |a: u8, b: u8| -> u8 {
-        let a = a as u16;
-        let b = b as u16;
-        let result = a * (100 - pos) + b * pos;
-        (result / 100) as u8
-    };
+        self.generate_palette_weighted(&colors, PALETTE_SIZE, self.seed)
+            .ok_or("Failed to generate palette")
+    }
 
-    (
-        interpolate(f.0, s.0),
-        interpolate(f.1, s.1),
-        interpolate(f.2, s.2),
-    )
+    pub fn average_color(&self, path: &str) -> Result<(u8, u8, u8), &'static str> {
+        let colors = self.load_colors(path)?;
+
+        if colors.is_empty() {
+            return Err("No colors left after filtering");
+        }
+
+        let sum = colors.iter().fold((0.0, 0.0, 0.0), |sum, c| {
+            let linear: LinSrgb = Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_linear();
+            (
+                sum.0 + linear.red,
+                sum.1 + linear.green,
+                sum.2 + linear.blue,
+            )
+        });
+        let count = colors.len() as f32;
+
+        let rgb_u8: Srgb<u8> =
+            Srgb::from_linear(LinSrgb::new(sum.0 / count, sum.1 / count, sum.2 / count));
+
+        Ok((rgb_u8.red, rgb_u8.green, rgb_u8.blue))
+    }
+
+    pub fn region_color(&self, img: &DynamicImage, region: Region) -> (u8, u8, u8) {
+        let (width, height) = (img.width(), img.height());
+        let (w, h) = ((width / 10).max(1), (height / 10).max(1));
+
+        let (x, y) = match region {
+            Region::TopLeft => (0, 0),
+            Region::TopRight => (width - w, 0),
+            Region::BottomLeft => (0, height - h),
+            Region::BottomRight => (width - w, height - h),
+            Region::Center => ((width - w) / 2, (height - h) / 2),
+        };
+
+        let view = img.crop_imm(x, y, w, h).to_rgb8();
+        let sum = view.pixels().fold((0.0, 0.0, 0.0), |sum, p| {
+            let linear: LinSrgb = Srgb::new(p[0], p[1], p[2])
+                .into_format::<f32>()
+                .into_linear();
+            (
+                sum.0 + linear.red,
+                sum.1 + linear.green,
+                sum.2 + linear.blue,
+            )
+        });
+        let count = (w * h) as f32;
+
+        let rgb_u8: Srgb<u8> =
+            Srgb::from_linear(LinSrgb::new(sum.0 / count, sum.1 / count, sum.2 / count));
+
+        (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
+    }
+
+    pub fn with_image_bg(&self, images: &[Decoded]) -> Rwal {
+        let mut rwal = self.clone();
+
+        if let Some(region) = self.bg_from_corner
+            && let Some((path, img)) = images.first()
+        {
+            rwal.bg_color = self.region_color(img, region);
+            log::info!(
+                "Sampled bg {} from the {} of {}",
+                rgb_to_hex(rwal.bg_color),
+                region,
+                path
+            );
+        }
+
+        rwal
+    }
+
+    pub fn generate_colorscheme(&self, paths: &[String]) -> Result<Colorscheme, String> {
+        let images = self.load_images(paths)?;
+        let palette = self.generate_raw_palette(&images)?;
+        Ok(self.with_image_bg(&images).build_colorscheme(&palette))
+    }
+
+    pub fn generate_raw_palette(&self, images: &[Decoded]) -> Result<Vec<(u8, u8, u8)>, String> {
+        if self.min_colors < PALETTE_SIZE {
+            return Err(format!(
+                "min_colors must be at least {} (got {})",
+                PALETTE_SIZE, self.min_colors
+            ));
+        }
+
+        let thumbs = self.load_all_thumbs(images)?;
+
+        if self.passthrough {
+            // Up to a full terminal palette passes through, so a 16 color swatch
+            // strip keeps its first swatches instead of being clustered.
+            let limit = 2 * self.min_colors;
+            let mut distinct = Vec::new();
+            for p in thumbs.iter().flat_map(|thumb| thumb.pixels()) {
+                let color = (p[0], p[1], p[2]);
+                if distinct.len() > limit {
+                    break;
+                }
+                if !distinct.contains(&color) {
+                    distinct.push(color);
+                }
+            }
+
+            if !distinct.is_empty() && distinct.len() <= limit {
+                log::info!("Image has {} colors, using them as is", distinct.len());
+                return Ok((0..self.min_colors)
+                    .map(|i| distinct[i % distinct.len()])
+                    .collect());
+            }
+
+            log::debug!("Image has more than {} colors, quantizing", limit);
+        }
+
+        let colors = self.prepare_all_colors(thumbs);
+
+        let weighted = |seed| self.generate_palette_weighted(&colors, self.min_colors, seed);
+        let weighted_colors =
+            |weighted: &Vec<WeightedColor>| weighted.iter().map(|&(color, _)| color).collect();
+
+        if let Some(harmony) = self.harmony {
+            let mut weighted = self.retry(weighted, weighted_colors)?;
+            weighted.sort_by(|f, s| s.1.total_cmp(&f.1));
+
+            let dominant = weighted
+                .into_iter()
+                .take(2)
+                .map(|(color, _)| color)
+                .collect::<Vec<(u8, u8, u8)>>();
+            log::info!("Building {} palette", harmony);
+
+            let palette = harmony.rebuild(&dominant);
+            if palette.is_empty() {
+                return Err("Failed to generate palette".into());
+            }
+
+            return Ok(palette);
+        }
+
+        if self.order_by_dominance {
+            let mut weighted = self.retry(weighted, weighted_colors)?;
+            weighted.sort_by(|f, s| s.1.total_cmp(&f.1));
+
+            return Ok(weighted.into_iter().map(|(color, _)| color).collect());
+        }
+
+        let mut palette = self.retry(
+            |seed| self.generate_palette(&colors, self.min_colors, seed),
+            |palette| palette.clone(),
+        )?;
+
+        if let Some(threshold) = self.threshold_colors {
+            palette = self.replace_similar(&colors, palette, threshold);
+        }
+
+        self.check_count(palette.len())?;
+
+        Ok(palette)
+    }
+
+    fn check_count(&self, got: usize) -> Result<(), String> {
+        if got < self.min_colors {
+            return Err(format!(
+                "Not enough colors generated (got {}, needed {})",
+                got, self.min_colors
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn replace_similar(
+        &self,
+        colors: &[(u8, u8, u8)],
+        palette: Vec<(u8, u8, u8)>,
+        threshold: f32,
+    ) -> Vec<(u8, u8, u8)> {
+        let lab =
+            |c: (u8, u8, u8)| -> Lab { Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_color() };
+        let distinct = |kept: &[(u8, u8, u8)], c: (u8, u8, u8)| {
+            kept.iter().all(|&k| delta_e(lab(k), lab(c)) >= threshold)
+        };
+
+        let mut extra = None;
+        let mut kept: Vec<(u8, u8, u8)> = Vec::with_capacity(palette.len());
+
+        for &color in &palette {
+            if distinct(&kept, color) {
+                kept.push(color);
+                continue;
+            }
+
+            let candidates = extra.get_or_insert_with(|| {
+                self.generate_palette(colors, palette.len() * 3, self.seed)
+                    .unwrap_or_default()
+            });
+
+            match candidates
+                .iter()
+                .position(|&c| !palette.contains(&c) && distinct(&kept, c))
+            {
+                Some(i) => {
+                    log::debug!(
+                        "Replacing {} with {}",
+                        rgb_to_hex(color),
+                        rgb_to_hex(candidates[i])
+                    );
+                    kept.push(candidates.remove(i));
+                }
+                None => {
+                    log::warn!(
+                        "No cluster is at least {} from the palette, keeping {}",
+                        threshold,
+                        rgb_to_hex(color)
+                    );
+                    kept.push(color);
+                }
+            }
+        }
+
+        kept
+    }
+
+    pub fn build_colorscheme(&self, palette: &[(u8, u8, u8)]) -> Colorscheme {
+        self.build_colorscheme_explained(palette).0
+    }
+
+    pub fn build_colorscheme_explained(
+        &self,
+        palette: &[(u8, u8, u8)],
+    ) -> (Colorscheme, Vec<String>) {
+        let mut notes = Vec::new();
+        let normalized;
+        let palette = if self.normalize_lightness {
+            normalized = normalize_lightness(palette, self.target_lightness);
+            &normalized
+        } else {
+            palette
+        };
+        let unsorted = palette;
+        let mut palette = if self.order_by_dominance {
+            palette.to_vec()
+        } else if self.pin_bg_fg {
+            let mut pinned = palette.to_vec();
+            let middle = sort_palette(&palette[1..7], self.sort_by);
+            pinned[1..7].copy_from_slice(&middle);
+            pinned
+        } else {
+            sort_palette(palette, self.sort_by)
+        };
+
+        if let Some(min_distance) = self.min_distance {
+            palette = separate_colors(&palette, min_distance);
+        }
+
+        let roles = if self.pin_bg_fg { unsorted } else { &palette };
+
+        let order = if self.order_by_dominance {
+            "in dominance order".to_string()
+        } else if self.pin_bg_fg {
+            format!("with palette[1..7] sorted by {}", self.sort_by)
+        } else {
+            format!("sorted by {}", self.sort_by)
+        };
+        let order = match self.min_distance {
+            Some(min_distance) => format!("{}, separated by at least {}", order, min_distance),
+            None => order,
+        };
+        let order = if self.normalize_lightness {
+            format!(
+                "{}, oklch lightness set to {}",
+                order, self.target_lightness
+            )
+        } else {
+            order
+        };
+        notes.push(format!(
+            "palette = {} ({})",
+            palette
+                .iter()
+                .map(|c| rgb_to_hex(*c))
+                .collect::<Vec<String>>()
+                .join(" "),
+            order
+        ));
+
+        let mix_colors = if self.linear_mix {
+            mix_colors_linear
+        } else {
+            mix_colors
+        };
+        let mixed = if self.linear_mix {
+            "mixed in linear light"
+        } else {
+            "mixed"
+        };
+        let derived = |slot: &str, name: &str, color, mode: MixMode, idx: usize, strength| {
+            let source = format!(
+                "{}palette[{}] {}",
+                if self.pin_bg_fg { "unsorted " } else { "" },
+                idx,
+                rgb_to_hex(roles[idx])
+            );
+            match mode {
+                MixMode::Fixed => format!(
+                    "{} = {} {} {} {}% with {}",
+                    slot,
+                    name,
+                    rgb_to_hex(color),
+                    mixed,
+                    strength,
+                    source
+                ),
+                MixMode::Darken => format!("{} = {} darkened {}%", slot, source, strength),
+                MixMode::Lighten => format!("{} = {} lightened {}%", slot, source, strength),
+            }
+        };
+
+        let (bg_idx, fg_idx) = if self.auto_bg_fg {
+            let by_luminance = |f: &usize, s: &usize| {
+                relative_luminance(roles[*f]).total_cmp(&relative_luminance(roles[*s]))
+            };
+            let darkest = (0..roles.len()).min_by(by_luminance).unwrap_or(0);
+            let lightest = (0..roles.len()).max_by(by_luminance).unwrap_or(0);
+
+            if relative_luminance(self.bg_color) > relative_luminance(self.fg_color) {
+                (lightest, darkest)
+            } else {
+                (darkest, lightest)
+            }
+        } else {
+            (self.bg_idx, self.fg_idx)
+        };
+
+        let bg = self
+            .bg_mode
+            .derive(self.bg_color, roles[bg_idx], self.bg_strength, mix_colors);
+        let fg = self
+            .fg_mode
+            .derive(self.fg_color, roles[fg_idx], self.fg_strength, mix_colors);
+
+        notes.push(derived(
+            "t0",
+            "bg_color",
+            self.bg_color,
+            self.bg_mode,
+            bg_idx,
+            self.bg_strength,
+        ));
+        for (slot, color) in palette.iter().enumerate().take(7).skip(1) {
+            notes.push(format!(
+                "t{} = palette[{}] {}",
+                slot,
+                slot,
+                rgb_to_hex(*color)
+            ));
+        }
+        notes.push(derived(
+            "t7",
+            "fg_color",
+            self.fg_color,
+            self.fg_mode,
+            fg_idx,
+            self.fg_strength,
+        ));
+        for slot in 0..8 {
+            let amount = if slot == 0 || slot == 7 { 10 } else { 30 };
+            notes.push(format!(
+                "t{} = t{} {} {}% with white",
+                slot + 8,
+                self.light_map[slot],
+                mixed,
+                amount
+            ));
+        }
+
+        let base = [
+            bg, palette[1], palette[2], palette[3], palette[4], palette[5], palette[6], fg,
+        ];
+        let light = |slot: usize| {
+            let amount = if slot == 0 || slot == 7 { 10 } else { 30 };
+            mix_colors(base[self.light_map[slot]], (255, 255, 255), amount)
+        };
+
+        let mut colorscheme = Colorscheme {
+            t0: base[0],
+            t1: base[1],
+            t2: base[2],
+            t3: base[3],
+            t4: base[4],
+            t5: base[5],
+            t6: base[6],
+            t7: base[7],
+            t8: light(0),
+            t9: light(1),
+            t10: light(2),
+            t11: light(3),
+            t12: light(4),
+            t13: light(5),
+            t14: light(6),
+            t15: light(7),
+            cursor: base[7],
+            base_only: self.base_only,
+        };
+
+        if self.hue_rotate != 0.0 {
+            colorscheme = colorscheme.rotate_hue(self.hue_rotate);
+            notes.push(format!("all colors rotated by {} degrees", self.hue_rotate));
+        }
+
+        if let Some(bias_hue) = self.bias_hue {
+            colorscheme = colorscheme.bias_toward_hue(bias_hue, self.bias_strength);
+            notes.push(format!(
+                "all hues pulled {}% toward {} degrees",
+                (self.bias_strength * 100.0).round(),
+                bias_hue
+            ));
+        }
+
+        if let Some(temperature) = self.temperature {
+            colorscheme = colorscheme.adjust_temperature(temperature);
+            notes.push(format!("all colors shifted to {}K", temperature));
+        }
+
+        if self.light {
+            colorscheme = colorscheme.for_light_background();
+            notes.push(
+                "t1-6 and t9-14 darkened and desaturated for the light background".to_string(),
+            );
+        }
+
+        if self.pywal_order {
+            colorscheme = colorscheme.pywal_order();
+            notes.push("t1-6 and t9-14 reordered from darkest to lightest".to_string());
+        }
+
+        colorscheme.cursor = self
+            .cursor_color
+            .or(colorscheme.into_array().get(self.cursor_idx).copied())
+            .unwrap_or(colorscheme.t7);
+
+        notes.push(match self.cursor_color {
+            Some(cursor) => format!("cursor = cursor_color {}", rgb_to_hex(cursor)),
+            None => format!("cursor = t{}", self.cursor_idx),
+        });
+
+        (colorscheme, notes)
+    }
+
+    pub fn generate_from_color(&self, color: (u8, u8, u8)) -> Colorscheme {
+        let hsv = to_hsv(color);
+        let variations = [
+            (0.0, 1.0, 0.35),
+            (-30.0, 1.0, 1.0),
+            (30.0, 1.0, 1.0),
+            (0.0, 1.0, 1.0),
+            (150.0, 0.9, 0.9),
+            (180.0, 1.0, 1.0),
+            (210.0, 0.9, 0.9),
+            (0.0, 0.3, 1.2),
+        ];
+
+        let palette = variations
+            .iter()
+            .map(|&(hue, saturation, value)| {
+                from_hsv(Hsv::new(
+                    hsv.hue + RgbHue::from_degrees(hue),
+                    (hsv.saturation * saturation).clamp(0.0, 1.0),
+                    (hsv.value * value).clamp(0.0, 1.0),
+                ))
+            })
+            .collect::<Vec<(u8, u8, u8)>>();
+
+        self.build_colorscheme(&palette)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Colorscheme {
+    pub t0: (u8, u8, u8),
+    pub t1: (u8, u8, u8),
+    pub t2: (u8, u8, u8),
+    pub t3: (u8, u8, u8),
+    pub t4: (u8, u8, u8),
+    pub t5: (u8, u8, u8),
+    pub t6: (u8, u8, u8),
+    pub t7: (u8, u8, u8),
+
+    pub t8: (u8, u8, u8),
+    pub t9: (u8, u8, u8),
+    pub t10: (u8, u8, u8),
+    pub t11: (u8, u8, u8),
+    pub t12: (u8, u8, u8),
+    pub t13: (u8, u8, u8),
+    pub t14: (u8, u8, u8),
+    pub t15: (u8, u8, u8),
+
+    pub cursor: (u8, u8, u8),
+    pub base_only: bool,
+}
+
+impl Colorscheme {
+    pub fn html_preview(&self) -> String {
+        const DIV: &str = include_str!("./div.html");
+        const PREV: &str = include_str!("./preview.html");
+
+        let mut dark_divs = Vec::new();
+        let mut light_divs = Vec::new();
+
+        let bg = self.t0;
+        let fg = self.t7;
+
+        let dark = [
+            self.t0, self.t1, self.t2, self.t3, self.t4, self.t5, self.t6, self.t7,
+        ];
+        let light = [
+            self.t8, self.t9, self.t10, self.t11, self.t12, self.t13, self.t14, self.t15,
+        ];
+
+        for c in dark {
+            let div = DIV
+                .replace("R", &c.0.to_string())
+                .replace("G", &c.1.to_string())
+                .replace("B", &c.2.to_string());
+            dark_divs.push(div);
+        }
+
+        if !self.base_only {
+            for c in light {
+                let div = DIV
+                    .replace("R", &c.0.to_string())
+                    .replace("G", &c.1.to_string())
+                    .replace("B", &c.2.to_string());
+                light_divs.push(div);
+            }
+        }
+
+        PREV.replace("{{DDIV}}", &dark_divs.join(""))
+            .replace("{{LDIV}}", &light_divs.join(""))
+            .replace("{{BR}}", &bg.0.to_string())
+            .replace("{{BG}}", &bg.1.to_string())
+            .replace("{{BB}}", &bg.2.to_string())
+            .replace("{{FR}}", &fg.0.to_string())
+            .replace("{{FG}}", &fg.1.to_string())
+            .replace("{{FB}}", &fg.2.to_string())
+    }
+
+    pub fn simulate(&self, kind: Simulation) -> Colorscheme {
+        self.map(|c| kind.apply(c))
+    }
+
+    pub fn accent(&self) -> (u8, u8, u8) {
+        let candidates = [self.t1, self.t2, self.t3, self.t4, self.t5, self.t6];
+
+        let score = |c: &(u8, u8, u8)| {
+            let hsv = to_hsv(*c);
+            hsv.saturation * hsv.value
+        };
+
+        candidates
+            .iter()
+            .filter(|c| to_hsv(**c).saturation >= 0.1)
+            .max_by(|f, s| score(f).total_cmp(&score(s)))
+            .or(candidates
+                .iter()
+                .max_by(|f, s| score(f).total_cmp(&score(s))))
+            .copied()
+            .unwrap_or(self.t1)
+    }
+
+    pub fn diff(&self, other: &Colorscheme) -> [f32; 16] {
+        let to_lab =
+            |c: (u8, u8, u8)| -> Lab { Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_color() };
+        let (f, s) = (self.into_array(), other.into_array());
+
+        std::array::from_fn(|i| delta_e(to_lab(f[i]), to_lab(s[i])))
+    }
+
+    pub fn contrast_ratio(a: (u8, u8, u8), b: (u8, u8, u8)) -> f32 {
+        let (a, b) = (relative_luminance(a), relative_luminance(b));
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    pub fn to_sequences(self) -> String {
+        let mut sequences = self
+            .into_array()
+            .iter()
+            .take(self.color_count())
+            .enumerate()
+            .map(|(i, color)| format!("\x1b]4;{};{}\x1b\\", i, rgb_to_hex(*color)))
+            .collect::<String>();
+
+        sequences.push_str(&format!("\x1b]10;{}\x1b\\", rgb_to_hex(self.t7)));
+        sequences.push_str(&format!("\x1b]11;{}\x1b\\", rgb_to_hex(self.t0)));
+        sequences.push_str(&format!("\x1b]12;{}\x1b\\", rgb_to_hex(self.cursor)));
+
+        sequences
+    }
+
+    pub fn color_count(self) -> usize {
+        if self.base_only { 8 } else { 16 }
+    }
+
+    pub fn to_hex_lines(self) -> String {
+        self.to_lines(ColorStyle::default())
+    }
+
+    pub fn to_lines(self, style: ColorStyle) -> String {
+        self.into_array()
+            .into_iter()
+            .take(self.color_count())
+            .map(|c| style.color(c))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn to_labeled(self, style: ColorStyle) -> String {
+        let label = |i: usize| match i {
+            0 => " (background)",
+            7 => " (foreground)",
+            8 => " (bright background)",
+            15 => " (bright foreground)",
+            _ => "",
+        };
+
+        let mut lines = self
+            .into_array()
+            .into_iter()
+            .take(self.color_count())
+            .enumerate()
+            .map(|(i, color)| format!("{} # color{}{}", style.color(color), i, label(i)))
+            .collect::<Vec<String>>();
+        lines.push(format!("{} # cursor", style.color(self.cursor)));
+
+        lines.join("\n")
+    }
+
+    pub fn to_xresources(self, style: ColorStyle) -> String {
+        let mut xresources = format!(
+            "*.background: {}\n*.foreground: {}\n*.cursorColor: {}\n",
+            style.hex(self.t0),
+            style.hex(self.t7),
+            style.hex(self.cursor)
+        );
+
+        for (i, color) in self
+            .into_array()
+            .iter()
+            .take(self.color_count())
+            .enumerate()
+        {
+            xresources.push_str(&format!("*.color{}: {}\n", i, style.hex(*color)));
+        }
+
+        xresources
+    }
+
+    pub fn to_windows_terminal(self, style: ColorStyle) -> String {
+        const NAMES: [&str; 16] = [
+            "black",
+            "red",
+            "green",
+            "yellow",
+            "blue",
+            "purple",
+            "cyan",
+            "white",
+            "brightBlack",
+            "brightRed",
+            "brightGreen",
+            "brightYellow",
+            "brightBlue",
+            "brightPurple",
+            "brightCyan",
+            "brightWhite",
+        ];
+
+        let mut entries = vec![
+            ("name", "rwal".to_string()),
+            ("background", style.hex(self.t0)),
+            ("foreground", style.hex(self.t7)),
+            ("cursorColor", style.hex(self.cursor)),
+            ("selectionBackground", style.hex(self.t8)),
+        ];
+        let colors = self.into_array();
+        entries.extend(
+            NAMES
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| (name, style.hex(colors[i % self.color_count()]))),
+        );
+
+        let entries = entries
+            .iter()
+            .map(|(key, value)| format!("    \"{}\": \"{}\"", key, value))
+            .collect::<Vec<String>>()
+            .join(",\n");
+
+        format!("{{\n{}\n}}\n", entries)
+    }
+
+    fn base16_slots(self) -> Vec<(u8, u8, u8)> {
+        let ramp = |i: u16| mix_colors(self.t0, self.t7, (i * 100 / 7) as u8);
+
+        vec![
+            self.t0,
+            ramp(1),
+            self.t8,
+            ramp(3),
+            ramp(5),
+            self.t7,
+            mix_colors(self.t7, self.t15, 50),
+            self.t15,
+            self.t1,
+            mix_colors(self.t1, self.t3, 50),
+            self.t3,
+            self.t2,
+            self.t6,
+            self.t4,
+            self.t5,
+            mix_colors(self.t1, self.t0, 50),
+        ]
+    }
+
+    fn scheme_yaml(style: ColorStyle, name: &str, slots: &[(u8, u8, u8)]) -> String {
+        let mut yaml = format!("scheme: \"{}\"\nauthor: \"rwal\"\n", name);
+
+        for (i, color) in slots.iter().enumerate() {
+            yaml.push_str(&format!(
+                "base{:02X}: \"{}\"\n",
+                i,
+                style.hex(*color).trim_start_matches('#')
+            ));
+        }
+
+        yaml
+    }
+
+    pub fn to_base16(self, style: ColorStyle) -> String {
+        Self::scheme_yaml(style, "rwal", &self.base16_slots())
+    }
+
+    pub fn to_base24(self, style: ColorStyle) -> String {
+        let mut slots = self.base16_slots();
+        slots.extend([
+            mix_colors(self.t0, (0, 0, 0), 25),
+            mix_colors(self.t0, (0, 0, 0), 50),
+            self.t9,
+            self.t11,
+            self.t10,
+            self.t14,
+            self.t12,
+            self.t13,
+        ]);
+
+        Self::scheme_yaml(style, "rwal", &slots)
+    }
+
+    pub fn format(self, format: Format, style: ColorStyle) -> String {
+        match format {
+            Format::Hex => self.to_lines(style),
+            Format::Labeled => self.to_labeled(style),
+            Format::Xresources => self.to_xresources(style),
+            Format::WindowsTerminal => self.to_windows_terminal(style),
+            Format::Base16 => self.to_base16(style),
+            Format::Base24 => self.to_base24(style),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn lighten(&self, pct: u8) -> Colorscheme {
+        self.map(|c| mix_colors(c, (255, 255, 255), pct))
+    }
+
+    #[allow(dead_code)]
+    pub fn darken(&self, pct: u8) -> Colorscheme {
+        self.map(|c| mix_colors(c, (0, 0, 0), pct))
+    }
+
+    pub fn rotate_hue(&self, degrees: f32) -> Colorscheme {
+        let degrees = degrees.rem_euclid(360.0);
+
+        self.map(|c| {
+            let mut hsv = to_hsv(c);
+            hsv.hue = RgbHue::from_degrees((hsv.hue.into_degrees() + degrees).rem_euclid(360.0));
+            from_hsv(hsv)
+        })
+    }
+
+    pub fn bias_toward_hue(&self, target: f32, strength: f32) -> Colorscheme {
+        let strength = strength.clamp(0.0, 1.0);
+        if strength == 0.0 {
+            return *self;
+        }
+
+        self.map(|c| {
+            let mut hsv = to_hsv(c);
+            let hue = hsv.hue.into_positive_degrees();
+            let delta = (target - hue + 540.0).rem_euclid(360.0) - 180.0;
+            hsv.hue = RgbHue::from_degrees((hue + delta * strength).rem_euclid(360.0));
+            from_hsv(hsv)
+        })
+    }
+
+    pub fn adjust_temperature(&self, kelvin: f32) -> Colorscheme {
+        let (r, g, b) = blackbody(kelvin);
+        let (wr, wg, wb) = blackbody(6500.0);
+        let scale = |v: u8, f: f32, w: f32| (v as f32 * f / w).round().clamp(0.0, 255.0) as u8;
+
+        self.map(|c| (scale(c.0, r, wr), scale(c.1, g, wg), scale(c.2, b, wb)))
+    }
+
+    pub fn from_hex_lines(lines: &[String]) -> Result<Colorscheme, String> {
+        let count = lines.len();
+        if count != 8 && count != 16 {
+            return Err(format!("Expected 8 or 16 colors, found {}", count));
+        }
+
+        let mut colors = [(0, 0, 0); 16];
+        for (color, line) in colors.iter_mut().zip(lines) {
+            *color = hex_to_rgb(line.trim())?;
+        }
+
+        if count == 8 {
+            let (base, light) = colors.split_at_mut(8);
+            light.copy_from_slice(base);
+        }
+
+        let mut colorscheme = Self::from_array(colors);
+        colorscheme.base_only = count == 8;
+
+        Ok(colorscheme)
+    }
+
+    pub fn from_array(colors: [(u8, u8, u8); 16]) -> Self {
+        Self {
+            t0: colors[0],
+            t1: colors[1],
+            t2: colors[2],
+            t3: colors[3],
+            t4: colors[4],
+            t5: colors[5],
+            t6: colors[6],
+            t7: colors[7],
+            t8: colors[8],
+            t9: colors[9],
+            t10: colors[10],
+            t11: colors[11],
+            t12: colors[12],
+            t13: colors[13],
+            t14: colors[14],
+            t15: colors[15],
+            cursor: colors[7],
+            base_only: false,
+        }
+    }
+
+    pub fn for_light_background(self) -> Colorscheme {
+        let mut colors = self.into_array();
+
+        for i in (1..7).chain(9..15) {
+            let mut hsv = to_hsv(colors[i]);
+            hsv.value = hsv.value.min(if i < 8 { 0.55 } else { 0.65 });
+            hsv.saturation = hsv.saturation.min(0.8);
+
+            while hsv.value > 0.0 && Self::contrast_ratio(from_hsv(hsv), colors[0]) < 4.5 {
+                hsv.value = (hsv.value - 0.05).max(0.0);
+            }
+
+            colors[i] = from_hsv(hsv);
+        }
+
+        Colorscheme {
+            cursor: self.cursor,
+            base_only: self.base_only,
+            ..Self::from_array(colors)
+        }
+    }
+
+    pub fn pywal_order(self) -> Colorscheme {
+        let mut colors = self.into_array();
+        let mut order = [1, 2, 3, 4, 5, 6];
+        order.sort_by(|&f, &s| {
+            relative_luminance(colors[f]).total_cmp(&relative_luminance(colors[s]))
+        });
+
+        let original = colors;
+        for (slot, &i) in order.iter().enumerate() {
+            colors[slot + 1] = original[i];
+            colors[slot + 9] = original[i + 8];
+        }
+
+        Colorscheme {
+            cursor: self.cursor,
+            base_only: self.base_only,
+            ..Self::from_array(colors)
+        }
+    }
+
+    pub fn merge_from(self, base: Colorscheme, indices: &[usize]) -> Colorscheme {
+        let generated = self.into_array();
+        let mut colors = base.into_array();
+
+        for &i in indices.iter().filter(|&&i| i < 16) {
+            colors[i] = generated[i];
+        }
+
+        Colorscheme {
+            cursor: base.cursor,
+            base_only: self.base_only,
+            ..Self::from_array(colors)
+        }
+    }
+
+    fn map<F: Fn((u8, u8, u8)) -> (u8, u8, u8)>(&self, f: F) -> Colorscheme {
+        Colorscheme {
+            cursor: f(self.cursor),
+            base_only: self.base_only,
+            ..Self::from_array(self.into_array().map(&f))
+        }
+    }
+
+    pub fn into_array(self) -> [(u8, u8, u8); 16] {
+        [
+            self.t0, self.t1, self.t2, self.t3, self.t4, self.t5, self.t6, self.t7, self.t8,
+            self.t9, self.t10, self.t11, self.t12, self.t13, self.t14, self.t15,
+        ]
+    }
+}
+
+impl Display for Colorscheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, color) in self.into_array().into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "color{}: {}", i, rgb_to_hex(color))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Hue,
+    Luminance,
+    Saturation,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hue" => Ok(SortBy::Hue),
+            "luminance" => Ok(SortBy::Luminance),
+            "saturation" => Ok(SortBy::Saturation),
+            _ => Err(format!("Unknown sort order: {}", s)),
+        }
+    }
+}
+
+impl Display for SortBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortBy::Hue => "hue",
+            SortBy::Luminance => "luminance",
+            SortBy::Saturation => "saturation",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClampMode {
+    #[default]
+    Hard,
+    Soft,
+}
+
+impl ClampMode {
+    fn apply(self, value: f32, min: f32, max: f32) -> f32 {
+        match self {
+            ClampMode::Hard => value.clamp(min, max),
+            ClampMode::Soft => min + value.clamp(0.0, 1.0) * (max - min),
+        }
+    }
+}
+
+impl FromStr for ClampMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hard" => Ok(ClampMode::Hard),
+            "soft" => Ok(ClampMode::Soft),
+            _ => Err(format!("Unknown clamp mode: {}", s)),
+        }
+    }
+}
+
+impl Display for ClampMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClampMode::Hard => "hard",
+            ClampMode::Soft => "soft",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterSpace {
+    #[default]
+    Hsv,
+    Hsl,
+    Lab,
+}
+
+impl FilterSpace {
+    const MAX_CHROMA: f32 = 128.0;
+
+    fn axes(self, c: Hsv) -> (f32, f32) {
+        match self {
+            FilterSpace::Hsv => (c.saturation, c.value),
+            FilterSpace::Hsl => {
+                let hsl = Hsl::from_color(c);
+                (hsl.saturation, hsl.lightness)
+            }
+            FilterSpace::Lab => {
+                let lch = Lch::from_color(c);
+                (lch.chroma / Self::MAX_CHROMA, lch.l / 100.0)
+            }
+        }
+    }
+
+    fn with_axes(self, c: Hsv, saturation: f32, value: f32) -> Srgb<f32> {
+        match self {
+            FilterSpace::Hsv => Srgb::from_color(Hsv::new(c.hue, saturation, value)),
+            FilterSpace::Hsl => {
+                let hsl = Hsl::from_color(c);
+                Srgb::from_color(Hsl::new(hsl.hue, saturation, value))
+            }
+            FilterSpace::Lab => {
+                let lch = Lch::from_color(c);
+                Srgb::from_color(Lch::new(
+                    value * 100.0,
+                    saturation * Self::MAX_CHROMA,
+                    lch.hue,
+                ))
+            }
+        }
+    }
+}
+
+impl FromStr for FilterSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hsv" => Ok(FilterSpace::Hsv),
+            "hsl" => Ok(FilterSpace::Hsl),
+            "lab" => Ok(FilterSpace::Lab),
+            _ => Err(format!("Unknown filter space: {}", s)),
+        }
+    }
+}
+
+impl Display for FilterSpace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterSpace::Hsv => "hsv",
+            FilterSpace::Hsl => "hsl",
+            FilterSpace::Lab => "lab",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    #[default]
+    Hex,
+    Labeled,
+    Xresources,
+    WindowsTerminal,
+    Base16,
+    Base24,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Format::Hex),
+            "labeled" => Ok(Format::Labeled),
+            "xresources" => Ok(Format::Xresources),
+            "windows-terminal" => Ok(Format::WindowsTerminal),
+            "base16" => Ok(Format::Base16),
+            "base24" => Ok(Format::Base24),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Hex => "hex",
+            Format::Labeled => "labeled",
+            Format::Xresources => "xresources",
+            Format::WindowsTerminal => "windows-terminal",
+            Format::Base16 => "base16",
+            Format::Base24 => "base24",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HexCase {
+    #[default]
+    Lower,
+    Upper,
+}
+
+impl FromStr for HexCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lower" => Ok(HexCase::Lower),
+            "upper" => Ok(HexCase::Upper),
+            _ => Err(format!("Unknown hex case: {}", s)),
+        }
+    }
+}
+
+impl Display for HexCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexCase::Lower => "lower",
+            HexCase::Upper => "upper",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notation {
+    #[default]
+    Hex,
+    Rgb,
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Notation::Hex),
+            "rgb" => Ok(Notation::Rgb),
+            _ => Err(format!("Unknown color notation: {}", s)),
+        }
+    }
+}
+
+impl Display for Notation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notation::Hex => "hex",
+            Notation::Rgb => "rgb",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ColorStyle {
+    pub notation: Notation,
+    pub hex_case: HexCase,
+}
+
+impl ColorStyle {
+    pub fn color(self, c: (u8, u8, u8)) -> String {
+        match self.notation {
+            Notation::Hex => self.hex(c),
+            Notation::Rgb => rgb_to_css(c),
+        }
+    }
+
+    pub fn hex(self, c: (u8, u8, u8)) -> String {
+        match self.hex_case {
+            HexCase::Lower => rgb_to_hex(c),
+            HexCase::Upper => rgb_to_hex(c).to_uppercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "topleft" => Ok(Region::TopLeft),
+            "topright" => Ok(Region::TopRight),
+            "bottomleft" => Ok(Region::BottomLeft),
+            "bottomright" => Ok(Region::BottomRight),
+            "center" => Ok(Region::Center),
+            _ => Err(format!("Unknown region: {}", s)),
+        }
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Region::TopLeft => "topleft",
+            Region::TopRight => "topright",
+            Region::BottomLeft => "bottomleft",
+            Region::BottomRight => "bottomright",
+            Region::Center => "center",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MixMode {
+    #[default]
+    Fixed,
+    Darken,
+    Lighten,
+}
+
+impl MixMode {
+    fn derive(
+        &self,
+        color: (u8, u8, u8),
+        palette_color: (u8, u8, u8),
+        strength: u8,
+        mix_colors: MixFn,
+    ) -> (u8, u8, u8) {
+        let amount = strength.clamp(0, 100) as f32 / 100.0;
+
+        let mut hsv = to_hsv(palette_color);
+
+        match self {
+            MixMode::Fixed => return mix_colors(color, palette_color, strength),
+            MixMode::Darken => hsv.value *= 1.0 - amount,
+            MixMode::Lighten => hsv.value += (1.0 - hsv.value) * amount,
+        }
+
+        from_hsv(hsv)
+    }
+}
+
+impl FromStr for MixMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(MixMode::Fixed),
+            "darken" => Ok(MixMode::Darken),
+            "lighten" => Ok(MixMode::Lighten),
+            _ => Err(format!("Unknown mix mode: {}", s)),
+        }
+    }
+}
+
+impl Display for MixMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MixMode::Fixed => "fixed",
+            MixMode::Darken => "darken",
+            MixMode::Lighten => "lighten",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Simulation {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl Simulation {
+    const RGB_TO_LMS: [[f32; 3]; 3] = [
+        [17.8824, 43.5161, 4.11935],
+        [3.45565, 27.1554, 3.86714],
+        [0.0299566, 0.184309, 1.46709],
+    ];
+
+    const LMS_TO_RGB: [[f32; 3]; 3] = [
+        [0.080944, -0.130504, 0.116721],
+        [-0.010249, 0.054019, -0.113615],
+        [-0.000365, -0.004122, 0.693511],
+    ];
+
+    fn matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            Simulation::Protanopia => [[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Simulation::Deuteranopia => {
+                [[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]]
+            }
+            Simulation::Tritanopia => {
+                [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]]
+            }
+        }
+    }
+
+    fn apply(&self, c: (u8, u8, u8)) -> (u8, u8, u8) {
+        let mul = |m: [[f32; 3]; 3], v: [f32; 3]| -> [f32; 3] {
+            m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+        };
+
+        let linear: LinSrgb = Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_linear();
+
+        let lms = mul(Self::RGB_TO_LMS, [linear.red, linear.green, linear.blue]);
+        let lms = mul(self.matrix(), lms);
+        let [r, g, b] = mul(Self::LMS_TO_RGB, lms).map(|v| v.clamp(0.0, 1.0));
+
+        let srgb: Srgb<u8> = Srgb::from_linear(LinSrgb::new(r, g, b));
+        (srgb.red, srgb.green, srgb.blue)
+    }
+}
+
+impl FromStr for Simulation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "protanopia" => Ok(Simulation::Protanopia),
+            "deuteranopia" => Ok(Simulation::Deuteranopia),
+            "tritanopia" => Ok(Simulation::Tritanopia),
+            _ => Err(format!("Unknown simulation: {}", s)),
+        }
+    }
+}
+
+impl Display for Simulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Simulation::Protanopia => "protanopia",
+            Simulation::Deuteranopia => "deuteranopia",
+            Simulation::Tritanopia => "tritanopia",
+        }
+        .fmt(f)
+    }
+}
+
+fn blackbody(kelvin: f32) -> (f32, f32, f32) {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+
+    let g = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12216 * (t - 60.0).powf(-0.07551485)
+    };
+
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+
+    (
+        r.clamp(0.0, 255.0),
+        g.clamp(0.0, 255.0),
+        b.clamp(0.0, 255.0),
+    )
+}
+
+pub fn image_hash(path: &str) -> Result<u64, &'static str> {
+    let img = image::open(path)
+        .map_err(|_| "Failed to open image")?
+        .resize_exact(9, 8, image::imageops::Triangle)
+        .to_luma8();
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if img.get_pixel(x, y)[0] < img.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Ok(hash)
+}
+
+pub fn to_hsv(c: (u8, u8, u8)) -> Hsv {
+    let srgb_u8 = Srgb::new(c.0, c.1, c.2);
+    let srgb_f32: Srgb<f32> = srgb_u8.into_format();
+    Hsv::from_color(srgb_f32)
+}
+
+pub fn from_hsv(hsv: Hsv) -> (u8, u8, u8) {
+    let rgb: Srgb<f32> = Srgb::from_color(hsv);
+    let rgb_u8: Srgb<u8> = rgb.into_format();
+    (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
+}
+
+fn sort_palette(palette: &[(u8, u8, u8)], sort_by: SortBy) -> Vec<(u8, u8, u8)> {
+    match sort_by {
+        SortBy::Hue => sort_by_hue(palette),
+        SortBy::Luminance => sort_by_key(palette, relative_luminance),
+        SortBy::Saturation => sort_by_key(palette, |c| to_hsv(c).saturation),
+    }
+}
+
+fn sort_by_key<F: Fn((u8, u8, u8)) -> f32>(palette: &[(u8, u8, u8)], key: F) -> Vec<(u8, u8, u8)> {
+    let mut palette = palette.to_vec();
+    palette.sort_by(|f, s| key(*f).total_cmp(&key(*s)));
+    palette
+}
+
+fn relative_luminance(c: (u8, u8, u8)) -> f32 {
+    let linear: LinSrgb = Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_linear();
+    0.2126 * linear.red + 0.7152 * linear.green + 0.0722 * linear.blue
+}
+
+fn sort_by_hue(palette: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
+    let mut hsv_palette: Vec<Hsv> = palette
+        .iter()
+        .map(|c| {
+            let srgb_u8 = Srgb::new(c.0, c.1, c.2);
+            let srgb_f32: Srgb<f32> = srgb_u8.into_format();
+            Hsv::from_color(srgb_f32)
+        })
+        .collect();
+
+    let hue = |c: &Hsv| {
+        let hue: f32 = c.hue.into();
+        if hue.is_nan() { 0.0 } else { hue }
+    };
+
+    hsv_palette.sort_by(|f, s| {
+        hue(f)
+            .total_cmp(&hue(s))
+            .then(f.value.total_cmp(&s.value))
+            .then(f.saturation.total_cmp(&s.saturation))
+    });
+
+    hsv_palette
+        .into_iter()
+        .map(|hsv| {
+            let rgb: Srgb<f32> = Srgb::from_color(hsv);
+            let rgb_u8: Srgb<u8> = rgb.into_format();
+            (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
+        })
+        .collect()
+}
+
+fn separate_colors(palette: &[(u8, u8, u8)], min_distance: f32) -> Vec<(u8, u8, u8)> {
+    let mut lab_palette: Vec<Lab> = palette
+        .iter()
+        .map(|c| Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_color())
+        .collect();
+
+    for j in 1..lab_palette.len() {
+        for i in 0..j {
+            let (f, s) = (lab_palette[i], lab_palette[j]);
+            let distance = delta_e(f, s);
+
+            if distance >= min_distance {
+                continue;
+            }
+
+            let shift = min_distance - distance;
+            let up = s.l >= f.l;
+
+            let l = if up && s.l + shift <= 100.0 || !up && s.l - shift < 0.0 {
+                s.l + shift
+            } else {
+                s.l - shift
+            };
+
+            lab_palette[j].l = l.clamp(0.0, 100.0);
+        }
+    }
+
+    lab_palette
+        .into_iter()
+        .map(|lab| {
+            let rgb: Srgb<f32> = lab.into_color();
+            let rgb_u8: Srgb<u8> = rgb.into_format();
+            (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
+        })
+        .collect()
+}
+
+fn delta_e(f: Lab, s: Lab) -> f32 {
+    ((f.l - s.l).powi(2) + (f.a - s.a).powi(2) + (f.b - s.b).powi(2)).sqrt()
+}
+
+fn normalize_lightness(palette: &[(u8, u8, u8)], lightness: f32) -> Vec<(u8, u8, u8)> {
+    palette
+        .iter()
+        .map(|c| {
+            let mut oklch: Oklch = Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_color();
+            oklch.l = lightness;
+
+            let rgb = Srgb::from_color(oklch);
+            let rgb_u8: Srgb<u8> = Srgb::new(
+                rgb.red.clamp(0.0, 1.0),
+                rgb.green.clamp(0.0, 1.0),
+                rgb.blue.clamp(0.0, 1.0),
+            )
+            .into_format();
+
+            (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
+        })
+        .collect()
+}
+
+fn sobel(image: &RgbImage) -> Vec<f32> {
+    let gray = image::imageops::grayscale(image);
+    let (width, height) = gray.dimensions();
+
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        gray.get_pixel(x, y)[0] as f32
+    };
+
+    let magnitudes = (0..height as i64)
+        .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1);
+            (gx * gx + gy * gy).sqrt()
+        })
+        .collect::<Vec<f32>>();
+
+    let max = magnitudes.iter().copied().fold(0.0, f32::max);
+    if max == 0.0 {
+        return magnitudes;
+    }
+
+    magnitudes.into_iter().map(|m| m / max).collect()
+}
+
+type BackendJob<T> = fn(&dyn RwalBackend, &[(u8, u8, u8)], usize, u64) -> Option<T>;
+
+/// Hashes a packed rgb pixel with a single multiply. SipHash costs more than
+/// the HSV conversion it would save, so the memo in `prepare_colors` uses this.
+/// The map picks buckets from the low bits, so `finish` folds the well-mixed
+/// high half of the product into them.
+#[derive(Default)]
+struct PixelHasher(u64);
+
+impl std::hash::Hasher for PixelHasher {
+    fn finish(&self) -> u64 {
+        self.0 ^ (self.0 >> 32)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u32(b as u32);
+        }
+    }
+
+    fn write_u32(&mut self, v: u32) {
+        self.0 = (self.0 ^ v as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+type PixelMap<V> = HashMap<u32, V, std::hash::BuildHasherDefault<PixelHasher>>;
+
+fn pack_rgb(p: &image::Rgb<u8>) -> u32 {
+    u32::from_le_bytes([p[0], p[1], p[2], 0])
+}
+
+type MixFn = fn((u8, u8, u8), (u8, u8, u8), u8) -> (u8, u8, u8);
+
+fn mix_colors_linear(f: (u8, u8, u8), s: (u8, u8, u8), pos: u8) -> (u8, u8, u8) {
+    let pos = pos.clamp(0, 100) as f32 / 100.0;
+
+    let f: LinSrgb = Srgb::new(f.0, f.1, f.2).into_format::<f32>().into_linear();
+    let s: LinSrgb = Srgb::new(s.0, s.1, s.2).into_format::<f32>().into_linear();
+
+    let interpolate = |a: f32, b: f32| a * (1.0 - pos) + b * pos;
+
+    let mixed = LinSrgb::new(
+        interpolate(f.red, s.red),
+        interpolate(f.green, s.green),
+        interpolate(f.blue, s.blue),
+    );
+    let rgb_u8: Srgb<u8> = Srgb::from_linear(mixed);
+
+    (rgb_u8.red, rgb_u8.green, rgb_u8.blue)
+}
+
+fn mix_colors(f: (u8, u8, u8), s: (u8, u8, u8), pos: u8) -> (u8, u8, u8) {
+    let pos = pos.clamp(0, 100) as u16;
+
+    let interpolate = |a: u8, b: u8| -> u8 {
+        let a = a as u16;
+        let b = b as u16;
+        let result = a * (100 - pos) + b * pos;
+        (result / 100) as u8
+    };
+
+    (
+        interpolate(f.0, s.0),
+        interpolate(f.1, s.1),
+        interpolate(f.2, s.2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn rwal() -> Rwal {
+        Rwal::from(&Config::default())
+    }
+
+    fn image(colors: &[(u8, u8, u8)]) -> Vec<Decoded> {
+        vec![("test".to_string(), DynamicImage::ImageRgb8(row(colors)))]
+    }
+
+    #[test]
+    fn min_colors_boundary() {
+        let rwal = rwal();
+        assert_eq!(rwal.min_colors, PALETTE_SIZE);
+        assert!(rwal.check_count(PALETTE_SIZE).is_ok());
+        assert_eq!(
+            rwal.check_count(PALETTE_SIZE - 1),
+            Err("Not enough colors generated (got 7, needed 8)".to_string())
+        );
+    }
+
+    #[test]
+    fn min_colors_below_palette_size() {
+        let rwal = Rwal {
+            min_colors: PALETTE_SIZE - 1,
+            ..rwal()
+        };
+        assert!(rwal.generate_raw_palette(&image(&[(0, 0, 0)])).is_err());
+    }
+
+    fn scheme() -> Colorscheme {
+        Colorscheme::from_array(std::array::from_fn(|i| {
+            let i = i as u8;
+            (i * 16, 255 - i * 16, i * 7)
+        }))
+    }
+
+    #[test]
+    fn hex_lines_round_trip() {
+        let scheme = scheme();
+        let lines = scheme.into_array().map(rgb_to_hex);
+        let parsed = Colorscheme::from_hex_lines(&lines).unwrap();
+        assert_eq!(parsed.into_array(), scheme.into_array());
+    }
+
+    #[test]
+    fn hex_lines_are_validated() {
+        let lines = scheme().into_array().map(rgb_to_hex);
+        assert!(Colorscheme::from_hex_lines(&lines[..15]).is_err());
+
+        let mut with_cursor = lines.to_vec();
+        with_cursor.push(rgb_to_hex((255, 255, 255)));
+        assert!(Colorscheme::from_hex_lines(&with_cursor).is_err());
+        assert!(Colorscheme::from_hex_lines(&with_cursor[8..]).is_err());
+
+        let mut lines = lines.to_vec();
+        lines[3] = "#12345g".to_string();
+        assert!(Colorscheme::from_hex_lines(&lines).is_err());
+    }
+
+    #[test]
+    fn display_lists_every_color() {
+        let shown = scheme().to_string();
+        let lines = shown.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 16);
+        assert_eq!(lines[0], "color0: #00ff00");
+        assert_eq!(lines[15], "color15: #f00f69");
+    }
+
+    #[test]
+    fn linear_mix_is_brighter_than_naive() {
+        let (gray, white) = ((128, 128, 128), (255, 255, 255));
+        assert_eq!(mix_colors(gray, white, 50), (191, 191, 191));
+
+        let linear = mix_colors_linear(gray, white, 50);
+        assert_eq!(linear.0, linear.1);
+        assert_eq!(linear.1, linear.2);
+        assert!((202..=206).contains(&linear.0), "{:?}", linear);
+
+        assert_eq!(mix_colors_linear(gray, white, 0), gray);
+        assert_eq!(mix_colors_linear(gray, white, 100), white);
+    }
+
+    #[test]
+    fn sort_by_hue_orders_grays_by_value() {
+        let grays = [200, 10, 120, 255, 0, 60, 120, 180].map(|v| (v, v, v));
+        let sorted = sort_by_hue(&grays);
+        assert_eq!(
+            sorted,
+            [0, 10, 60, 120, 120, 180, 200, 255].map(|v| (v, v, v))
+        );
+
+        let mut reversed = grays;
+        reversed.reverse();
+        assert_eq!(sort_by_hue(&reversed), sorted);
+    }
+
+    #[test]
+    fn pin_bg_fg_picks_roles_before_sorting() {
+        let palette = [
+            (0, 255, 0),
+            (255, 0, 0),
+            (0, 0, 255),
+            (255, 255, 0),
+            (0, 255, 255),
+            (255, 0, 255),
+            (255, 128, 0),
+            (32, 32, 32),
+        ];
+        let rwal = Rwal {
+            bg_strength: 100,
+            fg_idx: 7,
+            fg_strength: 100,
+            ..rwal()
+        };
+        let sorted = sort_palette(&palette, rwal.sort_by);
+        assert_ne!(sorted[0], palette[0]);
+
+        let scheme = rwal.build_colorscheme(&palette);
+        assert_eq!(scheme.t0, sorted[0]);
+        assert_eq!(scheme.t7, sorted[7]);
+
+        let pinned = Rwal {
+            pin_bg_fg: true,
+            ..rwal
+        }
+        .build_colorscheme(&palette);
+        assert_eq!(pinned.t0, palette[0]);
+        assert_eq!(pinned.t7, palette[7]);
+        assert_eq!(
+            [
+                pinned.t1, pinned.t2, pinned.t3, pinned.t4, pinned.t5, pinned.t6
+            ],
+            sort_palette(&palette[1..7], rwal.sort_by)[..]
+        );
+    }
+
+    fn unfiltered() -> Rwal {
+        Rwal {
+            clamp_saturation: false,
+            clamp_value: false,
+            skip_saturation: false,
+            skip_value: false,
+            ..rwal()
+        }
+    }
+
+    fn passthrough() -> Rwal {
+        Rwal {
+            passthrough: true,
+            image_resize: (32, 1),
+            ..unfiltered()
+        }
+    }
+
+    fn strip(count: u8) -> Vec<(u8, u8, u8)> {
+        (0..count).map(|i| (i * 15, 255 - i * 15, 128)).collect()
+    }
+
+    #[test]
+    fn passthrough_keeps_swatch_colors() {
+        let rwal = passthrough();
+
+        let few = strip(5);
+        let palette = rwal.generate_raw_palette(&image(&few)).unwrap();
+        assert_eq!(
+            palette,
+            (0..PALETTE_SIZE).map(|i| few[i % 5]).collect::<Vec<_>>()
+        );
+
+        let full = strip(2 * PALETTE_SIZE as u8);
+        let palette = rwal.generate_raw_palette(&image(&full)).unwrap();
+        assert_eq!(palette, full[..PALETTE_SIZE]);
+    }
+
+    #[test]
+    fn passthrough_quantizes_above_the_limit() {
+        let rwal = passthrough();
+        let many = image(&strip(2 * PALETTE_SIZE as u8 + 1));
+
+        let quantized = Rwal {
+            passthrough: false,
+            ..rwal.clone()
+        };
+        let palette = rwal.generate_raw_palette(&many).unwrap();
+        assert_ne!(palette, strip(PALETTE_SIZE as u8));
+        assert_eq!(quantized.generate_raw_palette(&many), Ok(palette));
+    }
+
+    #[test]
+    fn average_color_is_linear() {
+        let path = std::env::temp_dir().join(format!("rwal-average-{}.png", std::process::id()));
+        RgbImage::from_fn(2, 1, |x, _| image::Rgb([if x == 0 { 0 } else { 255 }; 3]))
+            .save(&path)
+            .unwrap();
+
+        let average = unfiltered().average_color(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(average, Ok((188, 188, 188)));
+    }
+
+    fn rainbow() -> Colorscheme {
+        Colorscheme::from_array(std::array::from_fn(|i| {
+            from_hsv(Hsv::new(i as f32 * 22.5, 1.0, 1.0))
+        }))
+    }
+
+    #[test]
+    fn full_hue_bias_collapses_hues() {
+        let biased = rainbow().bias_toward_hue(200.0, 1.0);
+        for c in biased.into_array() {
+            let hue = to_hsv(c).hue.into_positive_degrees();
+            assert!((hue - 200.0).abs() < 1.0, "{:?} has hue {}", c, hue);
+        }
+
+        let unbiased = rainbow().bias_toward_hue(200.0, 0.0);
+        assert_eq!(unbiased.into_array(), rainbow().into_array());
+    }
+
+    fn row(colors: &[(u8, u8, u8)]) -> RgbImage {
+        let mut image = RgbImage::new(colors.len() as u32, 1);
+        for (i, c) in colors.iter().enumerate() {
+            image.put_pixel(i as u32, 0, image::Rgb([c.0, c.1, c.2]));
+        }
+        image
+    }
+
+    #[test]
+    fn skip_inclusive_keeps_boundary_pixels() {
+        let pixels = [(0, 0, 0), (255, 255, 255), (255, 0, 0), (128, 64, 64)];
+        let rwal = Rwal {
+            skip_saturation: true,
+            saturation_skip: (0.0, 1.0),
+            skip_value: true,
+            value_skip: (0.0, 1.0),
+            ..unfiltered()
+        };
+
+        assert_eq!(rwal.prepare_colors(row(&pixels)), [(128, 64, 64)]);
+
+        let inclusive = Rwal {
+            skip_inclusive: true,
+            ..rwal
+        };
+        assert_eq!(inclusive.prepare_colors(row(&pixels)), pixels);
+    }
+
+    #[test]
+    fn skip_keeps_or_removes_the_range() {
+        let (gray, pink, red) = ((128, 128, 128), (255, 128, 128), (255, 0, 0));
+        let rwal = Rwal {
+            skip_saturation: true,
+            saturation_skip: (0.3, 0.7),
+            ..unfiltered()
+        };
+
+        assert_eq!(rwal.prepare_colors(row(&[gray, pink, red])), [pink]);
+
+        let inverted = Rwal {
+            skip_invert: true,
+            ..rwal
+        };
+        assert_eq!(
+            inverted.prepare_colors(row(&[gray, pink, red])),
+            [gray, red]
+        );
+    }
+
+    fn yaml_slots(yaml: &str) -> Vec<(String, String)> {
+        let mut lines = yaml.lines();
+        assert_eq!(lines.next(), Some("scheme: \"rwal\""));
+        assert_eq!(lines.next(), Some("author: \"rwal\""));
+
+        lines
+            .map(|line| {
+                let (key, value) = line.split_once(": ").unwrap();
+                let value = value.strip_prefix('"').unwrap().strip_suffix('"').unwrap();
+                assert!(value.len() == 6 && value.chars().all(|c| c.is_ascii_hexdigit()));
+                (key.to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn base16_and_base24_yaml() {
+        let scheme = scheme();
+        let hex = |c| rgb_to_hex(c).trim_start_matches('#').to_string();
+
+        let base16 = yaml_slots(&scheme.to_base16(ColorStyle::default()));
+        let keys = base16
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            (0..16)
+                .map(|i| format!("base{:02X}", i))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(base16[0].1, hex(scheme.t0));
+        assert_eq!(base16[5].1, hex(scheme.t7));
+        assert_eq!(base16[8].1, hex(scheme.t1));
+
+        let base24 = yaml_slots(&scheme.to_base24(ColorStyle::default()));
+        assert_eq!(base24.len(), 24);
+        assert_eq!(base24[..16], base16[..]);
+        assert_eq!(base24[23].0, "base17");
+    }
+
+    #[test]
+    fn lighten_and_darken() {
+        let scheme = scheme();
+        assert_eq!(scheme.lighten(0).into_array(), scheme.into_array());
+        assert_eq!(scheme.darken(0).into_array(), scheme.into_array());
+        assert_eq!(scheme.lighten(100).into_array(), [(255, 255, 255); 16]);
+        assert_eq!(scheme.darken(100).into_array(), [(0, 0, 0); 16]);
+    }
+
+    #[test]
+    fn light_background_keeps_text_readable() {
+        for bg in [(255, 255, 255), (238, 232, 213), (250, 240, 200)] {
+            let mut colors = rainbow().into_array();
+            colors[0] = bg;
+            let light = Colorscheme::from_array(colors).for_light_background();
+
+            let colors = light.into_array();
+            for i in (1..7).chain(9..15) {
+                let ratio = Colorscheme::contrast_ratio(colors[i], bg);
+                assert!(
+                    ratio >= 4.5,
+                    "color{} {:?} on {:?} is {}",
+                    i,
+                    colors[i],
+                    bg,
+                    ratio
+                );
+            }
+        }
+    }
+
+    fn lab(c: (u8, u8, u8)) -> Lab {
+        Srgb::new(c.0, c.1, c.2).into_format::<f32>().into_color()
+    }
+
+    #[test]
+    fn min_distance_separates_near_duplicates() {
+        let grays = (0..8)
+            .map(|i| (120 + i, 120 + i, 120 + i))
+            .collect::<Vec<_>>();
+        let separated = separate_colors(&grays, 5.0);
+
+        for pair in separated.windows(2) {
+            assert!(delta_e(lab(pair[0]), lab(pair[1])) >= 4.5);
+        }
+    }
+
+    #[test]
+    fn min_distance_only_applies_when_set() {
+        let grays = (0..8)
+            .map(|i| (120 + i, 120 + i, 120 + i))
+            .collect::<Vec<_>>();
+        let rwal = rwal();
+        assert_eq!(rwal.min_distance, None);
+
+        let unset = rwal.build_colorscheme(&grays).into_array();
+        assert_eq!(unset[1..7], grays[1..7]);
+
+        let set = Rwal {
+            min_distance: Some(5.0),
+            ..rwal
+        }
+        .build_colorscheme(&grays)
+        .into_array();
+        assert_ne!(set[1..7], grays[1..7]);
+    }
+
+    fn color() -> impl Strategy<Value = (u8, u8, u8)> {
+        (any::<u8>(), any::<u8>(), any::<u8>())
+    }
+
+    fn bounds(min: f32) -> impl Strategy<Value = (f32, f32)> {
+        (min..=1.0f32, min..=1.0f32).prop_map(|(a, b)| (a.min(b), a.max(b)))
+    }
+
+    proptest! {
+        #[test]
+        fn mix_colors_endpoints(f in color(), s in color()) {
+            prop_assert_eq!(mix_colors(f, s, 0), f);
+            prop_assert_eq!(mix_colors(f, s, 100), s);
+        }
+
+        #[test]
+        fn mix_colors_is_monotonic(f in color(), s in color(), pos in 0u8..100) {
+            let channels = |c: (u8, u8, u8)| [c.0, c.1, c.2];
+            let (a, b) = (mix_colors(f, s, pos), mix_colors(f, s, pos + 1));
+            for ((a, b), (f, s)) in channels(a)
+                .into_iter()
+                .zip(channels(b))
+                .zip(channels(f).into_iter().zip(channels(s)))
+            {
+                if f <= s {
+                    prop_assert!(a <= b);
+                } else {
+                    prop_assert!(a >= b);
+                }
+            }
+        }
+
+        #[test]
+        fn prepare_colors_stays_within_clamps(
+            pixels in prop::collection::vec(color(), 1..64),
+            saturation in bounds(0.0),
+            value in bounds(0.2),
+        ) {
+            let rwal = Rwal {
+                clamp_saturation: true,
+                saturation_clamp: saturation,
+                clamp_value: true,
+                value_clamp: value,
+                skip_saturation: false,
+                skip_value: false,
+                ..rwal()
+            };
+
+            for c in rwal.prepare_colors(row(&pixels)) {
+                let hsv = Hsv::from_color(Srgb::new(c.0, c.1, c.2).into_format::<f32>());
+                prop_assert!(hsv.value >= value.0 - 0.005 && hsv.value <= value.1 + 0.005);
+                prop_assert!(
+                    hsv.saturation >= saturation.0 - 0.02
+                        && hsv.saturation <= saturation.1 + 0.02
+                );
+            }
+        }
+    }
 }
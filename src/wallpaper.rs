@@ -0,0 +1,98 @@
+use std::fmt::Display;
+use std::process::Command;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Setter {
+    Feh,
+    Swww,
+    Hyprpaper,
+    Swaybg,
+}
+
+impl Setter {
+    pub fn set(self, image: &str) {
+        if crate::http::is_url(image) {
+            log::warn!(
+                "Cannot set {} as the wallpaper, it is not a local file",
+                image
+            );
+            return;
+        }
+
+        log::info!("Setting wallpaper {} with {}", image, self);
+
+        let result = match self {
+            Setter::Feh => run(Command::new("feh").args(["--bg-fill", image])),
+            Setter::Swww => run(Command::new("swww").args(["img", image])),
+            Setter::Hyprpaper => run(Command::new("hyprctl").args(["hyprpaper", "preload", image]))
+                .and_then(|_| {
+                    run(Command::new("hyprctl").args([
+                        "hyprpaper",
+                        "wallpaper",
+                        &format!(",{}", image),
+                    ]))
+                }),
+            Setter::Swaybg => {
+                let _ = Command::new("pkill").arg("swaybg").status();
+                Command::new("swaybg")
+                    .args(["-m", "fill", "-i", image])
+                    .spawn()
+                    .map(|_| ())
+            }
+        };
+
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::warn!("{} is not installed, wallpaper not set", self.program());
+            }
+            Err(e) => log::warn!("Failed to set wallpaper with {}: {}", self, e),
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Setter::Feh => "feh",
+            Setter::Swww => "swww",
+            Setter::Hyprpaper => "hyprctl",
+            Setter::Swaybg => "swaybg",
+        }
+    }
+}
+
+fn run(command: &mut Command) -> std::io::Result<()> {
+    let status = command.status()?;
+
+    if !status.success() {
+        return Err(std::io::Error::other(format!("exited with {}", status)));
+    }
+
+    Ok(())
+}
+
+impl FromStr for Setter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "feh" => Ok(Setter::Feh),
+            "swww" => Ok(Setter::Swww),
+            "hyprpaper" => Ok(Setter::Hyprpaper),
+            "swaybg" => Ok(Setter::Swaybg),
+            _ => Err(format!("Unknown wallpaper setter: {}", s)),
+        }
+    }
+}
+
+impl Display for Setter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Setter::Feh => "feh",
+            Setter::Swww => "swww",
+            Setter::Hyprpaper => "hyprpaper",
+            Setter::Swaybg => "swaybg",
+        }
+        .fmt(f)
+    }
+}